@@ -0,0 +1,158 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/// Orientation to which Apple Books locks the display of the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrientationLock {
+    /// Only displayed in landscape orientation
+    LandscapeOnly,
+    /// Only displayed in portrait orientation
+    PortraitOnly,
+    /// Not locked
+    None,
+}
+
+impl OrientationLock {
+    /// The value of the `orientation-lock` option
+    const fn value(self) -> &'static str {
+        match self {
+            Self::LandscapeOnly => "landscape-only",
+            Self::PortraitOnly => "portrait-only",
+            Self::None => "none",
+        }
+    }
+}
+
+/// Options written in `META-INF/com.apple.ibooks.display-options.xml`, which is only
+/// read by Apple Books.
+///
+/// Options set to `None` are not written. This is designed to be used with the
+/// [`apple_display_options`](struct.Builder.html#method.apple_display_options) method
+/// of the builder.
+///
+/// # Example
+///
+/// ```
+/// use epub_builder::{AppleDisplayOptions, OrientationLock};
+///
+/// let options = AppleDisplayOptions::new()
+///     .fixed_layout(true)
+///     .open_to_spread(true)
+///     .orientation_lock(OrientationLock::LandscapeOnly);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppleDisplayOptions {
+    /// Whether the fonts embedded in the book are used instead of the ones chosen by
+    /// the user (default: `Some(true)`)
+    pub specified_fonts: Option<bool>,
+    /// Whether the book is a fixed-layout one
+    pub fixed_layout: Option<bool>,
+    /// Whether a fixed-layout book opens on a two-page spread in landscape orientation
+    pub open_to_spread: Option<bool>,
+    /// Orientation to which the display is locked
+    pub orientation_lock: Option<OrientationLock>,
+    /// Whether the book contains interactive (scripted) content
+    pub interactive: Option<bool>,
+}
+
+impl Default for AppleDisplayOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AppleDisplayOptions {
+    /// Creates new options, only setting `specified-fonts` to `true`
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            specified_fonts: Some(true),
+            fixed_layout: None,
+            open_to_spread: None,
+            orientation_lock: None,
+            interactive: None,
+        }
+    }
+
+    /// Sets whether the fonts embedded in the book are used
+    #[must_use]
+    pub const fn specified_fonts(mut self, enabled: bool) -> Self {
+        self.specified_fonts = Some(enabled);
+        self
+    }
+
+    /// Sets whether the book is a fixed-layout one
+    #[must_use]
+    pub const fn fixed_layout(mut self, enabled: bool) -> Self {
+        self.fixed_layout = Some(enabled);
+        self
+    }
+
+    /// Sets whether a fixed-layout book opens on a two-page spread
+    #[must_use]
+    pub const fn open_to_spread(mut self, enabled: bool) -> Self {
+        self.open_to_spread = Some(enabled);
+        self
+    }
+
+    /// Sets the orientation to which the display is locked
+    #[must_use]
+    pub const fn orientation_lock(mut self, orientation: OrientationLock) -> Self {
+        self.orientation_lock = Some(orientation);
+        self
+    }
+
+    /// Sets whether the book contains interactive content
+    #[must_use]
+    pub const fn interactive(mut self, enabled: bool) -> Self {
+        self.interactive = Some(enabled);
+        self
+    }
+
+    /// Render the `option` elements
+    pub(crate) fn render(&self) -> String {
+        let flags = [
+            ("specified-fonts", self.specified_fonts),
+            ("fixed-layout", self.fixed_layout),
+            ("open-to-spread", self.open_to_spread),
+            ("interactive", self.interactive),
+        ];
+        let mut options: Vec<String> = flags
+            .into_iter()
+            .filter_map(|(name, value)| {
+                value.map(|value| format!("<option name=\"{name}\">{value}</option>"))
+            })
+            .collect();
+        if let Some(orientation) = self.orientation_lock {
+            options.push(format!(
+                "<option name=\"orientation-lock\">{}</option>",
+                orientation.value()
+            ));
+        }
+        options.join("\n")
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+///                                  TESTS                                     //
+/////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn apple_display_options_render() {
+    assert_eq!(
+        AppleDisplayOptions::new().render(),
+        "<option name=\"specified-fonts\">true</option>"
+    );
+    let options = AppleDisplayOptions {
+        specified_fonts: None,
+        ..AppleDisplayOptions::new()
+    }
+    .fixed_layout(true)
+    .orientation_lock(OrientationLock::PortraitOnly);
+    assert_eq!(
+        options.render(),
+        "<option name=\"fixed-layout\">true</option>\n\
+         <option name=\"orientation-lock\">portrait-only</option>"
+    );
+}
@@ -2,7 +2,15 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with
 // this file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use once_cell::sync::Lazy;
+use regex::Regex;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::io::Read;
+
+/// Matches any tag
+static TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").expect("error compiling tag regex"));
 
 /// Escape quotes from the string
 pub fn escape_quote<'a, S: Into<Cow<'a, str>>>(s: S) -> Cow<'a, str> {
@@ -30,6 +38,130 @@ pub fn indent<S: AsRef<str>>(s: S, level: usize) -> String {
         .join("\n")
 }
 
+/// Matches the `id` attribute of an element
+static ID: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"\sid\s*=\s*(?:"([^"]*)"|'([^']*)')"#).expect("error compiling id regex")
+});
+
+/// Returns the values of the `id` attributes of a XHTML document, with the position of
+/// the first element having each of them
+pub fn ids(xhtml: &str) -> HashMap<String, usize> {
+    let mut ids = IdReader::new(xhtml.as_bytes());
+    io::copy(&mut ids, &mut io::sink()).expect("reading a string can't fail");
+    ids.into_ids()
+}
+
+/// Reads a XHTML document, collecting the values of its `id` attributes as it is read
+///
+/// Only the tag being read is kept in memory, so this can be used when a document is
+/// copied, without reading it whole.
+pub struct IdReader<R> {
+    inner: R,
+    /// The values of the `id` attributes, with the position of the first element
+    /// having each of them
+    ids: HashMap<String, usize>,
+    /// The tag being read, after its `<`, and the position of its `<`
+    tag: Option<(Vec<u8>, usize)>,
+    /// The quote of the attribute value being read, if any
+    quote: Option<u8>,
+    /// Number of bytes read
+    read: usize,
+}
+
+impl<R: Read> IdReader<R> {
+    /// Creates a new reader, collecting the ids of the document read from `inner`
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            ids: HashMap::new(),
+            tag: None,
+            quote: None,
+            read: 0,
+        }
+    }
+
+    /// Returns the ids of the document that was read
+    pub fn into_ids(self) -> HashMap<String, usize> {
+        self.ids
+    }
+
+    /// Collects the ids of the tags in `bytes`, which follow the ones already read
+    fn collect(&mut self, bytes: &[u8]) {
+        for (i, &b) in bytes.iter().enumerate() {
+            let Some((ref mut tag, start)) = self.tag else {
+                if b == b'<' {
+                    self.tag = Some((vec![], self.read + i));
+                }
+                continue;
+            };
+            match (self.quote, b) {
+                (Some(quote), _) if b == quote => self.quote = None,
+                (None, b'"' | b'\'') => self.quote = Some(b),
+                (None, b'>') => {
+                    let attributes = String::from_utf8_lossy(tag);
+                    for caps in ID.captures_iter(&attributes) {
+                        if let Some(m) = caps.get(1).or_else(|| caps.get(2)) {
+                            self.ids
+                                .entry(m.as_str().to_owned())
+                                .or_insert_with(|| start + 1 + m.start());
+                        }
+                    }
+                    self.tag = None;
+                    continue;
+                }
+                _ => {}
+            }
+            tag.push(b);
+        }
+        self.read += bytes.len();
+    }
+}
+
+impl<R: Read> Read for IdReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.collect(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Returns the value of the `id` attribute in the attributes of a tag, which must
+/// start with whitespace (as they do after the name of the element)
+pub fn id_attribute(attributes: &str) -> Option<&str> {
+    ID.captures(attributes)
+        .and_then(|caps| caps.get(1).or_else(|| caps.get(2)))
+        .map(|m| m.as_str())
+}
+
+/// Returns the text content of some XHTML markup, i.e. without tags and with entities
+/// decoded, and with whitespace collapsed
+pub fn plain_text(markup: &str) -> String {
+    let text = TAG.replace_all(markup, "");
+    html_escape::decode_html_entities(&text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Compute the href of the file `to` relative to the document `from`
+///
+/// Both paths are relative to the root of the package (i.e. the `OEBPS` directory).
+pub fn relative_href(from: &str, to: &str) -> String {
+    let from = from.replace('\\', "/");
+    let to = to.replace('\\', "/");
+    let mut from_dirs: Vec<&str> = from.split('/').collect();
+    from_dirs.pop();
+    let to_parts: Vec<&str> = to.split('/').collect();
+    let common = from_dirs
+        .iter()
+        .zip(to_parts.iter().take(to_parts.len() - 1))
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut parts: Vec<&str> = vec![".."; from_dirs.len() - common];
+    parts.extend_from_slice(&to_parts[common..]);
+    parts.join("/")
+}
+
 #[test]
 #[allow(clippy::disallowed_names)]
 fn test_escape() {
@@ -74,3 +206,70 @@ fn test_indent() {
     4.2 I"
     );
 }
+
+#[test]
+fn test_relative_href() {
+    assert_eq!(relative_href("chapter.xhtml", "notes.xhtml"), "notes.xhtml");
+    assert_eq!(
+        relative_href("text/ch1.xhtml", "notes.xhtml"),
+        "../notes.xhtml"
+    );
+    assert_eq!(
+        relative_href("text/ch1.xhtml", "text/ch2.xhtml"),
+        "ch2.xhtml"
+    );
+    assert_eq!(
+        relative_href("text/part/ch1.xhtml", "images/a.png"),
+        "../../images/a.png"
+    );
+    assert_eq!(
+        relative_href("nav.xhtml", "text/ch1.xhtml"),
+        "text/ch1.xhtml"
+    );
+}
+
+#[test]
+fn test_plain_text() {
+    assert_eq!(
+        plain_text("The <em>Great</em>\n Gatsby<sup>1</sup> &amp; co"),
+        "The Great Gatsby1 & co"
+    );
+}
+
+#[test]
+fn test_ids() {
+    let ids = ids("<p id=\"a\"><span data-id=\"b\"></span><a\nid='c'></a></p>");
+    assert_eq!(
+        ids,
+        HashMap::from([(String::from("a"), 7), (String::from("c"), 42)])
+    );
+}
+
+#[test]
+fn test_id_reader() {
+    let xhtml = "<p title='a > b' id=\"a\">id=\"b\"</p><!-- x --><a\nid='c'/>";
+    // Read in small chunks, so tags are split
+    let mut ids = IdReader::new(xhtml.as_bytes());
+    let mut copy = vec![];
+    let mut buf = [0; 3];
+    loop {
+        let n = ids.read(&mut buf).unwrap();
+        if n == 0 {
+            break;
+        }
+        copy.extend_from_slice(&buf[..n]);
+    }
+    assert_eq!(copy, xhtml.as_bytes());
+    let ids = ids.into_ids();
+    assert_eq!(
+        ids,
+        HashMap::from([(String::from("a"), 21), (String::from("c"), 51)])
+    );
+    assert_eq!(ids, self::ids(xhtml));
+}
+
+#[test]
+fn test_id_attribute() {
+    assert_eq!(id_attribute(" class=\"a\" id='b'"), Some("b"));
+    assert_eq!(id_attribute(" data-id=\"a\""), None);
+}
@@ -2,23 +2,45 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with
 // this file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use crate::headings;
+use crate::inline_toc::InlineToc;
+use crate::kepub;
+use crate::layout::Layout;
+use crate::media_overlay;
+use crate::nav_document;
+use crate::nav_list::{NavList, NavTarget};
+use crate::notes::{self, Note};
+use crate::page_list::{PageBreak, PageList};
+use crate::rendition::{self, RootFile};
 use crate::templates;
-use crate::toc::{Element, Toc};
+use crate::toc::{Element, PlayOrder, Toc};
 use crate::zip::Zip;
+use crate::AppleDisplayOptions;
+use crate::MediaOverlay;
+use crate::NavListKind;
+use crate::NoteStyle;
+use crate::Numbering;
+use crate::Obfuscation;
+use crate::Profile;
 use crate::ReferenceType;
+use crate::Rendition;
 use crate::{common, EpubContent};
 
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::io;
 use std::io::Read;
+use std::ops::RangeInclusive;
 use std::path::Path;
 
+use color_eyre::eyre::bail;
 use color_eyre::eyre::Context;
 use color_eyre::Result;
 use mustache::MapBuilder;
 
 /// Represents the EPUB version.
 ///
-/// Currently, this library supports EPUB 2.0.1 and 3.0.1.
+/// Currently, this library supports EPUB 2.0.1, 3.0.1 and 3.3.
 #[non_exhaustive]
 #[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Eq)]
 pub enum Version {
@@ -26,6 +48,20 @@ pub enum Version {
     V20,
     /// EPUB 3.0.1 format
     V30,
+    /// EPUB 3.3 format
+    ///
+    /// The package and the navigation document are the same as for `V30` (EPUB 3.3
+    /// keeps the `3.0` version number of the package), except that the legacy toc.ncx
+    /// and guide are not generated by default (see the `legacy_navigation` method of the
+    /// builder).
+    ///
+    /// EPUB 3.3 publications should also conform to EPUB Accessibility 1.1, whose
+    /// metadata (`accessMode`, `accessibilityFeature`, `accessibilityHazard` and
+    /// `accessibilitySummary`) depends on the content: it is not generated, and must be
+    /// set with `add_accessibility_metadata`. A warning is logged for each of these
+    /// properties that is missing. The other requirements of EPUB Accessibility (e.g.
+    /// alternative text for images) are not checked.
+    V33,
 }
 
 /// EPUB Metadata
@@ -36,9 +72,13 @@ struct Metadata {
     pub lang: String,
     pub generator: String,
     pub toc_name: String,
+    pub notes_title: String,
     pub description: Vec<String>,
     pub subject: Vec<String>,
     pub license: Option<String>,
+    pub uuid: uuid::Uuid,
+    pub media_active_class: String,
+    pub page_break_source: Option<String>,
 }
 
 impl Metadata {
@@ -50,9 +90,13 @@ impl Metadata {
             lang: String::from("en"),
             generator: String::from("Rust EPUB library"),
             toc_name: String::from("Table Of Contents"),
+            notes_title: String::from("Notes"),
             description: vec![],
             subject: vec![],
             license: None,
+            uuid: uuid::Uuid::new_v4(),
+            media_active_class: String::from("-epub-media-overlay-active"),
+            page_break_source: None,
         }
     }
 }
@@ -66,6 +110,12 @@ struct Content {
     pub cover: bool,
     pub reftype: Option<ReferenceType>,
     pub title: String,
+    pub overlay: Option<MediaOverlay>,
+    /// The ids of the elements of this document, with their position in it, if it is
+    /// known
+    pub ids: Option<HashMap<String, usize>>,
+    /// Whether this document is generated by the builder, e.g. the inline toc
+    pub generated: bool,
 }
 
 impl Content {
@@ -82,10 +132,21 @@ impl Content {
             cover: false,
             reftype: None,
             title: String::new(),
+            overlay: None,
+            ids: None,
+            generated: false,
         }
     }
 }
 
+/// A landmark added independently of the content reference types
+#[derive(Debug)]
+struct Landmark {
+    pub epub_type: String,
+    pub title: String,
+    pub href: String,
+}
+
 /// Epub Builder
 ///
 /// The main struct you'll need to use in this library. It is first created using
@@ -105,6 +166,7 @@ impl Content {
 /// builder.generate(&mut io::stdout()).unwrap();
 /// ```
 #[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Builder<Z: Zip> {
     version: Version,
     zip: Z,
@@ -112,7 +174,28 @@ pub struct Builder<Z: Zip> {
     metadata: Metadata,
     toc: Toc,
     stylesheet: bool,
-    inline_toc: bool,
+    inline_toc: Option<InlineToc>,
+    mini_tocs: Vec<String>,
+    legacy_navigation: Option<bool>,
+    profile: Profile,
+    apple_display_options: AppleDisplayOptions,
+    write_apple_display_options: Option<bool>,
+    kepub: bool,
+    layout: Layout,
+    accessibility: Vec<(String, String)>,
+    notes: Vec<Note>,
+    note_style: NoteStyle,
+    page_list: PageList,
+    nav_lists: Vec<NavList>,
+    landmarks: Vec<Landmark>,
+    nav_document: Option<String>,
+    rendition: Rendition,
+    root_files: Vec<RootFile>,
+    rendition_mapping: bool,
+    encryption: Vec<String>,
+    auto_toc: Option<RangeInclusive<i32>>,
+    /// Whether the EPUB was generated, after which the archive is complete
+    generated: bool,
 }
 
 /// Epub Builder Metadata Kinds
@@ -141,37 +224,353 @@ impl<Z: Zip> Builder<Z> {
     ///
     /// # Errors
     pub fn new(zip: Z) -> Result<Self> {
-        let mut epub = Self {
+        let epub = Self {
             version: Version::V20,
             zip,
             files: vec![],
             metadata: Metadata::new(),
             toc: Toc::new(),
             stylesheet: false,
-            inline_toc: false,
+            inline_toc: None,
+            mini_tocs: vec![],
+            legacy_navigation: None,
+            profile: Profile::Generic,
+            apple_display_options: AppleDisplayOptions::new(),
+            write_apple_display_options: None,
+            kepub: false,
+            layout: Layout::new(),
+            accessibility: vec![],
+            notes: vec![],
+            note_style: NoteStyle::Footnotes,
+            page_list: PageList::new(),
+            nav_lists: vec![
+                NavList::new(NavListKind::Illustrations),
+                NavList::new(NavListKind::Tables),
+            ],
+            landmarks: vec![],
+            nav_document: None,
+            rendition: Rendition::new(),
+            root_files: vec![],
+            rendition_mapping: false,
+            encryption: vec![],
+            auto_toc: None,
+            generated: false,
         };
 
-        epub.zip
-            .write_file("META-INF/container.xml", templates::CONTAINER)?;
-        epub.zip.write_file(
-            "META-INF/com.apple.ibooks.display-options.xml",
-            templates::IBOOKS,
-        )?;
-
         Ok(epub)
     }
 
+    /// Generates one EPUB per profile from the same book definition
+    ///
+    /// `define` is called once per profile and returns the builder of the book, whose
+    /// profile is then set before it is generated to the writer returned by `output`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use epub_builder::{Builder, EpubContent, Profile, ZipLibrary};
+    ///
+    /// let mut epubs = vec![Vec::<u8>::new(); 2];
+    /// let mut outputs = epubs.iter_mut();
+    /// Builder::generate_profiles(
+    ///     &[Profile::Kobo, Profile::Kindle],
+    ///     || {
+    ///         let mut builder = Builder::new(ZipLibrary::new()?)?;
+    ///         builder.add_content(EpubContent::new("ch1.xhtml", b"".as_ref()).title("One"))?;
+    ///         Ok(builder)
+    ///     },
+    ///     |_profile| Ok(outputs.next().unwrap()),
+    /// )
+    /// .unwrap();
+    /// assert!(epubs.iter().all(|epub| !epub.is_empty()));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if any of the books can't be defined or generated.
+    pub fn generate_profiles<F, G, W>(
+        profiles: &[Profile],
+        mut define: F,
+        mut output: G,
+    ) -> Result<()>
+    where
+        F: FnMut() -> Result<Self>,
+        G: FnMut(Profile) -> Result<W>,
+        W: io::Write,
+    {
+        for &profile in profiles {
+            let mut builder = define()?;
+            builder.profile(profile);
+            builder
+                .generate(output(profile)?)
+                .wrap_err_with(|| format!("error generating EPUB for profile {profile:?}"))?;
+        }
+        Ok(())
+    }
+
     /// Set EPUB version (default: V20)
     ///
     /// Supported versions are:
     ///
     /// * `V20`: EPUB 2.0.1
     /// * `V30`: EPUB 3.0.1
-    pub fn epub_version(&mut self, version: Version) -> &mut Self {
+    /// * `V33`: EPUB 3.3
+    pub const fn epub_version(&mut self, version: Version) -> &mut Self {
         self.version = version;
         self
     }
 
+    /// Sets whether the EPUB 2 compatibility artifacts, i.e. toc.ncx, the `toc`
+    /// attribute of the spine and the guide, are generated for EPUB 3
+    ///
+    /// They are deprecated, but some older reading systems still rely on them. By
+    /// default, they are generated for EPUB 3.0.1 but not for EPUB 3.3. This has no
+    /// effect on EPUB 2.0.1, which requires them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use epub_builder::{Builder, Version, ZipLibrary};
+    ///
+    /// // A lean EPUB 3.0.1 package, without toc.ncx
+    /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// builder.epub_version(Version::V30)
+    ///     .legacy_navigation(false);
+    /// ```
+    pub const fn legacy_navigation(&mut self, enabled: bool) -> &mut Self {
+        self.legacy_navigation = Some(enabled);
+        self
+    }
+
+    /// Sets the reading system targeted by the generated EPUB (default: `Generic`)
+    ///
+    /// See [`Profile`](enum.Profile.html) for the adjustments made by each profile.
+    pub const fn profile(&mut self, profile: Profile) -> &mut Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Sets the options written in `META-INF/com.apple.ibooks.display-options.xml`
+    ///
+    /// The file is then written whatever the profile. By default, it is written for
+    /// the `Generic` and `AppleBooks` profiles, with `specified-fonts` set to `true`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use epub_builder::{AppleDisplayOptions, Builder, ZipLibrary};
+    ///
+    /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// builder.apple_display_options(AppleDisplayOptions::new().interactive(true));
+    /// ```
+    pub const fn apple_display_options(&mut self, options: AppleDisplayOptions) -> &mut Self {
+        self.apple_display_options = options;
+        self.write_apple_display_options = Some(true);
+        self
+    }
+
+    /// Does not write `META-INF/com.apple.ibooks.display-options.xml`, whatever the
+    /// profile
+    pub const fn omit_apple_display_options(&mut self) -> &mut Self {
+        self.write_apple_display_options = Some(false);
+        self
+    }
+
+    /// Sets the directory containing the files of the package (default: `OEBPS`)
+    ///
+    /// # Errors
+    ///
+    /// Fails if files were already added, since they are written as soon as they are
+    /// added, or if `dir` is not a relative path to a directory other than `META-INF`.
+    pub fn root_dir<S: AsRef<str>>(&mut self, dir: S) -> Result<&mut Self> {
+        if !self.files.is_empty() {
+            bail!("the root directory must be set before adding files");
+        }
+        self.layout.set_root(dir.as_ref())?;
+        Ok(self)
+    }
+
+    /// Sets whether files are sorted into sub-directories of the root directory,
+    /// according to their media type (default: `false`)
+    ///
+    /// XHTML documents, including the generated ones, are then placed in `Text/`,
+    /// images in `Images/`, stylesheets in `Styles/` and fonts in `Fonts/`, e.g.
+    /// `images/map.png` is written to `Images/images/map.png`. Other files, as well as
+    /// content.opf, toc.ncx and nav.xhtml, stay at the root.
+    ///
+    /// Files are still referred to by their original paths when calling the methods
+    /// of the builder, and the hrefs of the generated files are rewritten to match.
+    /// Links inside the documents added with `add_content` are not: since all the
+    /// documents are moved to the same directory, links between them are still valid,
+    /// but links to images or stylesheets must take this layout into account.
+    ///
+    /// # Errors
+    ///
+    /// Fails if files were already added, since they are written as soon as they are
+    /// added.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use epub_builder::{Builder, ZipLibrary};
+    ///
+    /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// builder.root_dir("EPUB").unwrap()
+    ///     .organize_files(true).unwrap()
+    ///     // Written as EPUB/Images/cover.png
+    ///     .add_cover_image("cover.png", b"".as_ref(), "image/png").unwrap();
+    /// ```
+    pub fn organize_files(&mut self, enabled: bool) -> Result<&mut Self> {
+        if !self.files.is_empty() {
+            bail!("the layout of files must be set before adding files");
+        }
+        self.layout.organized = enabled;
+        Ok(self)
+    }
+
+    /// Sets the selection attributes of the current rendition
+    ///
+    /// They are written on its `rootfile` element in container.xml, and its layout,
+    /// if set, is also declared in its content.opf (EPUB 3 only). See
+    /// [`add_rendition`](#method.add_rendition) to add other renditions.
+    pub fn rendition(&mut self, rendition: Rendition) -> &mut Self {
+        self.rendition = rendition;
+        self
+    }
+
+    /// Starts a new rendition of the publication, whose files are written in `root_dir`
+    ///
+    /// The current package, i.e. content.opf, the navigation documents and the files
+    /// added until now, is written, and the builder is then reset for the new
+    /// rendition: its files, toc, notes, page list, navigation lists and landmarks start
+    /// empty. The metadata and the other settings of the builder are shared by all the
+    /// renditions. The first rendition is the default one for reading systems that
+    /// don't support multiple renditions.
+    ///
+    /// # Errors
+    ///
+    /// Fails for EPUB 2.0.1, if `root_dir` is not valid or is the directory of a
+    /// previous rendition, or if the current package can't be written.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use epub_builder::{Builder, EpubContent, Rendition, RenditionLayout, Version, ZipLibrary};
+    ///
+    /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// builder.epub_version(Version::V30)
+    ///     .rendition(Rendition::new().layout(RenditionLayout::Reflowable))
+    ///     .add_content(EpubContent::new("ch1.xhtml", b"".as_ref()).title("Chapter 1"))
+    ///     .unwrap()
+    ///     .add_rendition("FXL", Rendition::new()
+    ///         .layout(RenditionLayout::PrePaginated)
+    ///         .media("(orientation: landscape)"))
+    ///     .unwrap()
+    ///     .add_content(EpubContent::new("page1.xhtml", b"".as_ref()).title("Chapter 1"))
+    ///     .unwrap()
+    ///     .rendition_mapping(true);
+    /// let mut epub: Vec<u8> = vec![];
+    /// builder.generate(&mut epub).unwrap();
+    /// ```
+    pub fn add_rendition<S: AsRef<str>>(
+        &mut self,
+        root_dir: S,
+        rendition: Rendition,
+    ) -> Result<&mut Self> {
+        if self.version == Version::V20 {
+            bail!("multiple renditions are not supported by EPUB 2.0.1");
+        }
+        let mut layout = self.layout.clone();
+        layout.set_root(root_dir.as_ref())?;
+        let path = format!("{}/content.opf", layout.root);
+        if layout.root == self.layout.root || self.root_files.iter().any(|r| r.path == path) {
+            bail!(
+                "the directory '{}' is already used by a rendition",
+                layout.root
+            );
+        }
+        self.write_package()?;
+        self.finish_package();
+        self.reset_package();
+        self.layout = layout;
+        self.rendition = rendition;
+        Ok(self)
+    }
+
+    /// Sets whether a rendition mapping document is generated (default: `false`)
+    ///
+    /// This document, `mapping.xhtml` at the root of the container, links the
+    /// corresponding documents of the renditions, so that reading systems can switch
+    /// from one rendition to another at the same location. Documents are matched by
+    /// their position in the spine.
+    pub const fn rendition_mapping(&mut self, enabled: bool) -> &mut Self {
+        self.rendition_mapping = enabled;
+        self
+    }
+
+    /// Sets whether the EPUB is generated in the KEPUB format of Kobo devices
+    /// (default: `false`)
+    ///
+    /// When enabled, the documents added with `add_content` are rewritten as they are
+    /// added: their sentences and images are wrapped in `koboSpan` elements, and their
+    /// body in `book-columns` and `book-inner` divs. Kobo devices only recognize such
+    /// files if their name ends with `.kepub.epub`, see
+    /// [`file_extension`](#method.file_extension).
+    ///
+    /// This is usually combined with [`Profile::Kobo`](enum.Profile.html).
+    ///
+    /// # Errors
+    ///
+    /// Fails if documents were already added with `add_content`, since they are
+    /// written as soon as they are added.
+    pub fn kepub(&mut self, enabled: bool) -> Result<&mut Self> {
+        if self.files.iter().any(|f| f.itemref && !f.generated) {
+            bail!("the KEPUB mode must be set before adding documents");
+        }
+        self.kepub = enabled;
+        Ok(self)
+    }
+
+    /// The extension of the generated file, without the leading dot: `kepub.epub` in
+    /// KEPUB mode, `epub` otherwise
+    #[must_use]
+    pub const fn file_extension(&self) -> &'static str {
+        if self.kepub {
+            "kepub.epub"
+        } else {
+            "epub"
+        }
+    }
+
+    /// Adds accessibility metadata, e.g. `accessibilitySummary` or `accessibilityHazard`
+    ///
+    /// This is written as a `schema:{property}` meta in content.opf (EPUB 3 only).
+    /// Nothing is written by default: properties such as `accessMode` or
+    /// `accessibilityFeature` depend on the content, so they must be set with this
+    /// method, including for EPUB 3.3 (for which a warning is logged for each missing
+    /// property, see [`Version::V33`](enum.Version.html#variant.V33)).
+    ///
+    /// See <https://www.w3.org/TR/epub-a11y-11/> for the expected properties and values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use epub_builder::{Builder, Version, ZipLibrary};
+    ///
+    /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// builder.epub_version(Version::V33)
+    ///     .add_accessibility_metadata("accessibilityHazard", "none")
+    ///     .add_accessibility_metadata("accessibilitySummary", "A text-only novel.");
+    /// ```
+    pub fn add_accessibility_metadata<S1, S2>(&mut self, property: S1, value: S2) -> &mut Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        self.accessibility.push((property.into(), value.into()));
+        self
+    }
+
     /// Set some EPUB metadata
     ///
     /// For most metadata, this function will replace the existing metadata, but for subject, cteator and identifier who
@@ -230,6 +629,34 @@ impl<Z: Zip> Builder<Z> {
         self
     }
 
+    /// Sets the UUID of the EPUB.
+    ///
+    /// This is used as the unique identifier of the package (`dc:identifier`) and as
+    /// the key for font obfuscation. By default, a random (v4) UUID is generated when
+    /// the builder is created.
+    ///
+    /// # Errors
+    ///
+    /// Fails if fonts were already added with `add_font`, since they are obfuscated
+    /// with the previous identifier as soon as they are added.
+    pub fn set_uuid(&mut self, uuid: uuid::Uuid) -> Result<&mut Self> {
+        if !self.encryption.is_empty() {
+            bail!("the UUID must be set before adding obfuscated fonts");
+        }
+        self.metadata.uuid = uuid;
+        Ok(self)
+    }
+
+    /// Sets the CSS class applied by reading systems to the element currently
+    /// narrated by a media overlay (default: `-epub-media-overlay-active`).
+    ///
+    /// This is written in the `media:active-class` metadata of EPUB 3 files that
+    /// contain media overlays; your stylesheet should define it.
+    pub fn media_active_class<S: Into<String>>(&mut self, class: S) -> &mut Self {
+        self.metadata.media_active_class = class.into();
+        self
+    }
+
     /// Sets stylesheet of the EPUB.
     ///
     /// This content will be written in a `stylesheet.css` file; it is used by
@@ -250,179 +677,940 @@ impl<Z: Zip> Builder<Z> {
     ///
     /// The position where this table of contents will be inserted depends on when
     /// you call this method: if you call it before adding any content, it will be
-    /// at the beginning, if you call it after, it will be at the end.
+    /// at the beginning, if you call it after, it will be at the end. Use
+    /// `inline_toc_with` to set it explicitly, and for other options.
     pub fn inline_toc(&mut self) -> &mut Self {
-        self.inline_toc = true;
-        self.toc
-            .add(Element::new("toc.xhtml", self.metadata.toc_name.as_str()));
-        let mut file = Content::new("toc.xhtml", "application/xhtml+xml");
-        file.reftype = Some(ReferenceType::Toc);
-        file.title = self.metadata.toc_name.clone();
-        file.itemref = true;
-        self.files.push(file);
-        self
+        self.inline_toc_with(InlineToc::new())
     }
 
-    /// Add a resource to the EPUB file
-    ///
-    /// This resource can be a picture, a font, some CSS file, .... Unlike
-    /// `add_content`, files added this way won't appear in the linear
-    /// document.
+    /// Adds an inline toc in the document, with the given options.
     ///
-    /// Note that these files will automatically be inserted into an `OEBPS` directory,
-    /// so you don't need (and shouldn't) prefix your path with `OEBPS/`.
+    /// See [`InlineToc`](struct.InlineToc.html). If the inline toc was already added,
+    /// only its options are changed.
     ///
-    /// # Arguments
+    /// # Example
     ///
-    /// * `path`: the path where this file will be written in the EPUB OEBPS structure,
-    ///   e.g. `data/image_0.png`
-    /// * `content`: the resource to include
-    /// * `mime_type`: the mime type of this file, e.g. "image/png".
+    /// ```
+    /// use epub_builder::{Builder, InlineToc, ZipLibrary};
     ///
-    /// # Errors
-    pub fn add_resource<R, P, S>(&mut self, path: P, content: R, mime_type: S) -> Result<&mut Self>
-    where
-        R: Read,
-        P: AsRef<Path>,
-        S: Into<String>,
-    {
-        self.zip
-            .write_file(Path::new("OEBPS").join(path.as_ref()), content)?;
-        log::debug!("Add resource: {:?}", path.as_ref().display());
-        self.files.push(Content::new(
-            format!("{}", path.as_ref().display()),
-            mime_type,
-        ));
-        Ok(self)
+    /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// builder.inline_toc_with(InlineToc::new()
+    ///     .position(1)
+    ///     .depth(2)
+    ///     .template("<html><body><h1>{{{title}}}</h1>{{{content}}}</body></html>"));
+    /// ```
+    pub fn inline_toc_with(&mut self, options: InlineToc) -> &mut Self {
+        if self.inline_toc.is_none() {
+            // The title is set when the EPUB is generated
+            self.toc.add(Element::new("toc.xhtml", "").unnumbered());
+            let mut file = Content::new("toc.xhtml", "application/xhtml+xml");
+            file.reftype = Some(ReferenceType::Toc);
+            file.itemref = true;
+            file.generated = true;
+            self.files.push(file);
+        }
+        self.inline_toc = Some(options);
+        self
     }
 
-    /// Add a cover image to the EPUB.
-    ///
-    /// This works similarly to adding the image as a resource with the `add_resource`
-    /// method, except, it signals it in the Manifest section so it is displayed as the
-    /// cover by Ereaders
+    /// Adds a table of contents for a part of the book, listing the children of the toc
+    /// element whose url is `root`.
     ///
-    /// # Errors
-    pub fn add_cover_image<R, P, S>(
-        &mut self,
-        path: P,
-        content: R,
-        mime_type: S,
-    ) -> Result<&mut Self>
-    where
-        R: Read,
-        P: AsRef<Path>,
-        S: Into<String>,
-    {
-        self.zip
-            .write_file(Path::new("OEBPS").join(path.as_ref()), content)?;
-        let mut file = Content::new(format!("{}", path.as_ref().display()), mime_type);
-        file.cover = true;
-        self.files.push(file);
-        Ok(self)
-    }
-
-    /// Add a XHTML content file that will be added to the EPUB.
+    /// This page is inserted in the spine right after the document of this element
+    /// (e.g. the opening page of the part), and uses the book stylesheet. It doesn't
+    /// appear in the table of contents itself.
     ///
-    /// # Examples
+    /// # Example
     ///
     /// ```
-    /// # use epub_builder::{Builder, ZipLibrary, EpubContent};
-    /// let content = "Some content";
+    /// use epub_builder::{Builder, EpubContent, ZipLibrary};
+    ///
     /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
-    /// // Add a chapter that won't be added to the Table of Contents
-    /// builder.add_content(EpubContent::new("intro.xhtml", content.as_bytes())).unwrap();
+    /// builder.add_content(EpubContent::new("part_1.xhtml", "".as_bytes())
+    ///         .title("Part 1"))
+    ///     .unwrap()
+    ///     .add_content(EpubContent::new("chapter_1.xhtml", "".as_bytes())
+    ///         .title("Chapter 1")
+    ///         .level(2))
+    ///     .unwrap()
+    ///     .add_mini_toc("part_1.xhtml");
     /// ```
+    pub fn add_mini_toc<S: Into<String>>(&mut self, root: S) -> &mut Self {
+        let root = root.into();
+        if !self.mini_tocs.contains(&root) {
+            self.mini_tocs.push(root);
+        }
+        self
+    }
+
+    /// Sets where the notes added with `add_note` are written (default: `Footnotes`)
     ///
-    /// ```
-    /// # use epub_builder::{Builder, ZipLibrary, EpubContent, Element};
-    /// # let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
-    /// # let content = "Some content";
-    /// // Sets the title of a chapter so it is added to the Table of contents
-    /// // Also add information about its structure
-    /// builder.add_content(EpubContent::new("chapter_1.xhtml", content.as_bytes())
-    ///                      .title("Chapter 1")
-    ///                      .child(Element::new("chapter_1.xhtml#1", "1.1"))).unwrap();
-    /// ```
+    /// * `Footnotes`: notes are written as asides at the end of the document they
+    ///   belong to;
+    /// * `Endnotes`: notes are collected in a generated `endnotes.xhtml` document,
+    ///   added at the end of the book and to the table of contents.
     ///
-    /// ```
-    /// # use epub_builder::{Builder, ZipLibrary, EpubContent};
-    /// # let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
-    /// # let content = "Some content";
-    /// // Add a section, by setting the level to 2 (instead of the default value 1)
-    /// builder.add_content(EpubContent::new("section.xhtml", content.as_bytes())
-    ///                      .title("Section 1")
-    ///                      .level(2)).unwrap();
-    /// ```
+    /// Since it changes the links returned by `add_note`, this must be set before adding
+    /// any note.
+    pub const fn note_style(&mut self, style: NoteStyle) -> &mut Self {
+        self.note_style = style;
+        self
+    }
+
+    /// Sets the title of the generated endnotes document (default: "Notes")
+    pub fn notes_title<S: Into<String>>(&mut self, title: S) -> &mut Self {
+        self.metadata.notes_title = title.into();
+        self
+    }
+
+    /// Adds a note to a chapter, and returns the markup linking to it.
     ///
-    /// Note that these files will automatically be inserted into an `OEBPS` directory,
-    /// so you don't need (and shouldn't) prefix your path with `OEBPS/`.
+    /// The returned string is a link (`epub:type="noteref"` for EPUB 3) that must be
+    /// inserted in the text of the chapter, where the note is referenced. The note
+    /// itself (with a link back to this reference) is written either at the end of the
+    /// chapter or in an endnotes document, according to the `note_style` setting.
     ///
-    /// # See also
+    /// Notes are numbered from 1 in each chapter. Since the markup depends on them, the
+    /// EPUB version and the note style must be set before calling this method.
     ///
-    /// * [`EpubContent`](struct.EpubContent.html)
-    /// * the `add_resource` method, to add other resources in the EPUB file.
+    /// # Arguments
     ///
-    /// # Errors
-    pub fn add_content<R: Read>(&mut self, content: EpubContent<R>) -> Result<&mut Self> {
-        self.zip.write_file(
-            Path::new("OEBPS").join(content.toc.url.as_str()),
-            content.content,
-        )?;
-        let mut file = Content::new(content.toc.url.as_str(), "application/xhtml+xml");
-        file.itemref = true;
-        file.reftype = content.reftype;
-        if file.reftype.is_some() {
-            file.title = content.toc.title.clone();
-        }
-        self.files.push(file);
-        if !content.toc.title.is_empty() {
-            self.toc.add(content.toc);
-        }
-        Ok(self)
-    }
-
-    /// Generate the EPUB file and write it to the writer
+    /// * `chapter`: the path of the chapter referencing this note, as it will be given
+    ///   to `EpubContent::new`;
+    /// * `content`: the content of the note; it should be valid XHTML.
     ///
     /// # Example
     ///
     /// ```
-    /// # use epub_builder::{Builder, ZipLibrary};
+    /// # use epub_builder::{Builder, ZipLibrary, EpubContent};
     /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
-    /// // Write the EPUB file into a Vec<u8>
-    /// let mut epub: Vec<u8> = vec!();
+    /// let noteref = builder.add_note("chapter_1.xhtml", "<p>Some footnote</p>").unwrap();
+    /// let chapter = format!("<p>Some text{noteref}.</p>");
+    /// builder.add_content(EpubContent::new("chapter_1.xhtml", chapter.as_bytes())).unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// With footnotes, fails if the chapter has already been added, since notes can't be
+    /// inserted in it anymore. With both styles, `generate` fails if the chapter is never
+    /// added.
+    pub fn add_note<S1, S2>(&mut self, chapter: S1, content: S2) -> Result<String>
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        let chapter = chapter.into();
+        if self.note_style == NoteStyle::Footnotes && self.files.iter().any(|f| f.file == chapter) {
+            bail!("can't add footnote to '{chapter}': it has already been added to the EPUB");
+        }
+        let note = Note {
+            id: self.notes.len() + 1,
+            label: self.notes.iter().filter(|n| n.chapter == chapter).count() + 1,
+            chapter,
+            content: content.into(),
+        };
+        let href = match self.note_style {
+            NoteStyle::Footnotes => String::new(),
+            NoteStyle::Endnotes => common::relative_href(&note.chapter, notes::ENDNOTES_FILE),
+        };
+        let noteref = note.render_noteref(&href, self.version > Version::V20);
+        self.notes.push(note);
+        Ok(noteref)
+    }
+
+    /// Registers a page break of the print edition.
+    ///
+    /// Page breaks are listed, in the order they are added, in a `page-list` navigation
+    /// element of nav.xhtml (EPUB 3) and in the `pageList` of toc.ncx, so that reading
+    /// systems can display and go to the page numbers of the print edition.
+    ///
+    /// # Arguments
+    ///
+    /// * `label`: the page number, as printed, e.g. `12` or `xiv`;
+    /// * `href`: the location of the page break, e.g. `chapter_1.xhtml#page12`. The matching
+    ///   element should have an `epub:type="pagebreak"` attribute in EPUB 3.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use epub_builder::{Builder, ZipLibrary};
+    /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// builder.page_break_source("urn:isbn:9780000000000")
+    ///     .add_page_break("1", "chapter_1.xhtml#page1")
+    ///     .add_page_break("2", "chapter_1.xhtml#page2");
+    /// ```
+    pub fn add_page_break<S1, S2>(&mut self, label: S1, href: S2) -> &mut Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        self.page_list.pages.push(PageBreak {
+            label: label.into(),
+            href: href.into(),
+        });
+        self
+    }
+
+    /// Sets the source of the page breaks, i.e. an identifier of the print edition,
+    /// e.g. `urn:isbn:9780000000000`.
+    ///
+    /// It is written as a `dc:source` (with `source-of` pagination) and `pageBreakSource`
+    /// metadata.
+    pub fn page_break_source<S: Into<String>>(&mut self, source: S) -> &mut Self {
+        self.metadata.page_break_source = Some(source.into());
+        self
+    }
+
+    /// Adds an entry to a list of illustrations or tables.
+    ///
+    /// Non-empty lists are rendered as `<nav epub:type="loi">` and `<nav epub:type="lot">`
+    /// elements in nav.xhtml (EPUB 3), and as `navList`s in toc.ncx. See also the
+    /// `inline_nav_list` method to display them in the book.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind`: the list to add the entry to;
+    /// * `label`: the label of the entry, e.g. `Figure 1: a map`;
+    /// * `href`: the location of the figure or table, e.g. `chapter_1.xhtml#fig1`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use epub_builder::{Builder, ZipLibrary, NavListKind};
+    /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// builder.add_to_nav_list(NavListKind::Illustrations, "Figure 1", "chapter_1.xhtml#fig1")
+    ///     .add_to_nav_list(NavListKind::Tables, "Table 1", "chapter_1.xhtml#table1");
+    /// ```
+    pub fn add_to_nav_list<S1, S2>(&mut self, kind: NavListKind, label: S1, href: S2) -> &mut Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        self.nav_list_mut(kind).targets.push(NavTarget {
+            label: label.into(),
+            href: href.into(),
+        });
+        self
+    }
+
+    /// Sets the title of a list of illustrations or tables (default: "List of
+    /// Illustrations" and "List of Tables").
+    pub fn nav_list_title<S: Into<String>>(&mut self, kind: NavListKind, title: S) -> &mut Self {
+        self.nav_list_mut(kind).title = title.into();
+        self
+    }
+
+    /// Adds an inline page for a list of illustrations or tables in the document.
+    ///
+    /// Like `inline_toc`, the position of this page depends on when you call this method.
+    /// The list itself and its title are set when the EPUB is generated, so it contains
+    /// all the entries, even those added afterwards.
+    pub fn inline_nav_list(&mut self, kind: NavListKind) -> &mut Self {
+        self.nav_list_mut(kind).inline = true;
+        // The title is set when the EPUB is generated, see `title_nav_lists`
+        self.toc.add(Element::new(kind.file(), "").unnumbered());
+        let mut file = Content::new(kind.file(), "application/xhtml+xml");
+        file.reftype = Some(kind.reftype());
+        file.itemref = true;
+        file.generated = true;
+        self.files.push(file);
+        self
+    }
+
+    /// Returns the list of illustrations or tables
+    fn nav_list_mut(&mut self, kind: NavListKind) -> &mut NavList {
+        self.nav_lists
+            .iter_mut()
+            .find(|list| list.kind == kind)
+            .expect("all kinds of nav lists are created with the builder")
+    }
+
+    /// Adds an entry to the landmarks of the navigation document (EPUB 3 only).
+    ///
+    /// Landmarks are normally derived from the reference type of the content files (see
+    /// `EpubContent::reftype`), which can only point at whole files. This method allows to
+    /// add landmarks pointing anywhere, e.g. the start of the text in the middle of a file.
+    /// They are listed after the ones derived from content files.
+    ///
+    /// # Arguments
+    ///
+    /// * `epub_type`: the structural semantics of this landmark, e.g. `bodymatter` (see
+    ///   <https://idpf.github.io/epub-vocabs/structure/>);
+    /// * `title`: the label of this landmark;
+    /// * `href`: the location of this landmark, e.g. `chapter_1.xhtml#start`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use epub_builder::{Builder, ZipLibrary};
+    /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// builder.add_landmark("bodymatter", "Start of text", "front.xhtml#start");
+    /// ```
+    pub fn add_landmark<S1, S2, S3>(&mut self, epub_type: S1, title: S2, href: S3) -> &mut Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+        S3: Into<String>,
+    {
+        self.landmarks.push(Landmark {
+            epub_type: epub_type.into(),
+            title: title.into(),
+            href: href.into(),
+        });
+        self
+    }
+
+    /// Uses a hand-crafted navigation document instead of generating one.
+    ///
+    /// By default, `nav.xhtml` is rendered from the table of contents; with this method,
+    /// the given document is written instead. The toc is still used to generate `toc.ncx`
+    /// and, if requested, the inline table of contents.
+    ///
+    /// For EPUB 3, the document is checked when the EPUB is generated: it must declare the
+    /// XHTML and epub namespaces, and contain exactly one `toc` nav element and at most one
+    /// `landmarks` and `page-list` nav elements.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the content can't be read or isn't valid UTF-8.
+    pub fn nav_document<R: Read>(&mut self, mut content: R) -> Result<&mut Self> {
+        let mut nav = String::new();
+        content
+            .read_to_string(&mut nav)
+            .wrap_err("could not read navigation document")?;
+        self.nav_document = Some(nav);
+        Ok(self)
+    }
+
+    /// Add an entry to the table of contents, without any content
+    ///
+    /// This is mostly useful with [`Element::unlinked`](struct.Element.html#method.unlinked),
+    /// e.g. to group the following chapters (added with a level of 2) under a part that
+    /// has no page of its own.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use epub_builder::{Builder, Element, EpubContent, ZipLibrary};
+    ///
+    /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// builder.add_toc_element(Element::unlinked("Part One"))
+    ///     .add_content(EpubContent::new("chapter_1.xhtml", "".as_bytes())
+    ///         .title("Chapter 1")
+    ///         .level(2))
+    ///     .unwrap();
+    /// ```
+    pub fn add_toc_element(&mut self, element: Element) -> &mut Self {
+        self.toc.add(element);
+        self
+    }
+
+    /// Add all the entries of an existing table of contents, e.g. one imported with
+    /// [`Toc::from_nav`](struct.Toc.html#method.from_nav) or
+    /// [`Toc::from_ncx`](struct.Toc.html#method.from_ncx).
+    ///
+    /// The contents should then be added without a title, so they don't appear twice
+    /// in the table of contents.
+    pub fn add_toc(&mut self, toc: Toc) -> &mut Self {
+        for element in toc.elements {
+            self.toc.add(element);
+        }
+        self
+    }
+
+    /// Sets the maximum number of levels of the table of contents that are displayed,
+    /// both in toc.ncx and in nav.xhtml (default: no limit).
+    pub fn toc_depth(&mut self, depth: usize) -> &mut Self {
+        self.toc.max_depth(depth);
+        self
+    }
+
+    /// Number the entries of the table of contents
+    ///
+    /// The numbers are part of the labels in `nav.xhtml`, `toc.ncx` and the inline
+    /// table of contents. See [`Numbering`](enum.Numbering.html).
+    pub fn toc_numbering(&mut self, numbering: Numbering) -> &mut Self {
+        self.toc.numbering(numbering);
+        self
+    }
+
+    /// Build the table of contents from the headings of the documents
+    ///
+    /// When this is set, `add_content` looks for the `h1`–`h6` elements whose level is
+    /// in `levels` (e.g. `1..=3`), adds an `id` to those that have none, and builds the
+    /// toc entry of the document from them: the first heading gives the title of the
+    /// document, and the following ones become its children.
+    ///
+    /// A title set with `EpubContent::title` overrides the detected one, and
+    /// children set with `EpubContent::child` are kept instead of the detected ones.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use epub_builder::{Builder, EpubContent, ZipLibrary};
+    ///
+    /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// builder.auto_toc(1..=2)
+    ///     .add_content(EpubContent::new("chapter_1.xhtml",
+    ///         "<h1>Chapter 1</h1><h2>Section 1</h2>".as_bytes()))
+    ///     .unwrap();
+    /// ```
+    pub const fn auto_toc(&mut self, levels: RangeInclusive<i32>) -> &mut Self {
+        self.auto_toc = Some(levels);
+        self
+    }
+
+    /// Add a resource to the EPUB file
+    ///
+    /// This resource can be a picture, a font, some CSS file, .... Unlike
+    /// `add_content`, files added this way won't appear in the linear
+    /// document.
+    ///
+    /// Note that these files will automatically be inserted into the root directory of
+    /// the package (`OEBPS`, unless set with `root_dir`), so you don't need (and
+    /// shouldn't) prefix your path with `OEBPS/`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: the path where this file will be written in the EPUB OEBPS structure,
+    ///   e.g. `data/image_0.png`
+    /// * `content`: the resource to include
+    /// * `mime_type`: the mime type of this file, e.g. "image/png".
+    ///
+    /// # Errors
+    pub fn add_resource<R, P, S>(&mut self, path: P, content: R, mime_type: S) -> Result<&mut Self>
+    where
+        R: Read,
+        P: AsRef<Path>,
+        S: Into<String>,
+    {
+        let file = Content::new(format!("{}", path.as_ref().display()), mime_type);
+        self.zip.write_file(
+            self.layout.path(&self.layout.place(&file.file, &file.mime)),
+            content,
+        )?;
+        log::debug!("Add resource: {:?}", path.as_ref().display());
+        self.files.push(file);
+        Ok(self)
+    }
+
+    /// Add a cover image to the EPUB.
+    ///
+    /// This works similarly to adding the image as a resource with the `add_resource`
+    /// method, except, it signals it in the Manifest section so it is displayed as the
+    /// cover by Ereaders
+    ///
+    /// # Errors
+    pub fn add_cover_image<R, P, S>(
+        &mut self,
+        path: P,
+        content: R,
+        mime_type: S,
+    ) -> Result<&mut Self>
+    where
+        R: Read,
+        P: AsRef<Path>,
+        S: Into<String>,
+    {
+        let mut file = Content::new(format!("{}", path.as_ref().display()), mime_type);
+        self.zip.write_file(
+            self.layout.path(&self.layout.place(&file.file, &file.mime)),
+            content,
+        )?;
+        file.cover = true;
+        self.files.push(file);
+        Ok(self)
+    }
+
+    /// Add an obfuscated font to the EPUB.
+    ///
+    /// This works similarly to adding the font as a resource with the `add_resource`
+    /// method, except the font is obfuscated with the given algorithm, and a matching
+    /// entry is added to `META-INF/encryption.xml`, so reading systems know how to
+    /// restore it.
+    ///
+    /// Since obfuscation is keyed on the unique identifier of the book, the UUID must be
+    /// set (with `set_uuid`) before adding fonts.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use epub_builder::{Builder, ZipLibrary, Obfuscation};
+    /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// let dummy_font = "Not really an OpenType font";
+    /// builder.add_font("fonts/font.otf", dummy_font.as_bytes(), "font/otf", Obfuscation::Idpf)
+    ///     .unwrap();
+    /// ```
+    ///
+    /// # Errors
+    pub fn add_font<R, P, S>(
+        &mut self,
+        path: P,
+        mut content: R,
+        mime_type: S,
+        obfuscation: Obfuscation,
+    ) -> Result<&mut Self>
+    where
+        R: Read,
+        P: AsRef<Path>,
+        S: Into<String>,
+    {
+        let file = Content::new(format!("{}", path.as_ref().display()), mime_type);
+        let mut bytes = vec![];
+        content
+            .read_to_end(&mut bytes)
+            .wrap_err_with(|| format!("could not read font '{}'", file.file))?;
+        obfuscation
+            .obfuscate(&self.identifier(), &mut bytes)
+            .wrap_err_with(|| format!("could not obfuscate font '{}'", file.file))?;
+        let path = self.layout.path(&self.layout.place(&file.file, &file.mime));
+        self.zip.write_file(&path, bytes.as_slice())?;
+        self.encryption.push(format!(
+            "\
+<enc:EncryptedData>
+  <enc:EncryptionMethod Algorithm=\"{algorithm}\"/>
+  <enc:CipherData>
+    <enc:CipherReference URI=\"{uri}\"/>
+  </enc:CipherData>
+</enc:EncryptedData>",
+            algorithm = obfuscation.algorithm(),
+            // in the zip the path is always with forward slashes, on windows it is with backslashes
+            uri = common::escape_quote(format!("{}", path.display()).replace('\\', "/"))
+        ));
+        log::debug!("Add font: {:?}", file.file);
+        self.files.push(file);
+        Ok(self)
+    }
+
+    /// Add a XHTML content file that will be added to the EPUB.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub_builder::{Builder, ZipLibrary, EpubContent};
+    /// let content = "Some content";
+    /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// // Add a chapter that won't be added to the Table of Contents
+    /// builder.add_content(EpubContent::new("intro.xhtml", content.as_bytes())).unwrap();
+    /// ```
+    ///
+    /// ```
+    /// # use epub_builder::{Builder, ZipLibrary, EpubContent, Element};
+    /// # let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// # let content = "Some content";
+    /// // Sets the title of a chapter so it is added to the Table of contents
+    /// // Also add information about its structure
+    /// builder.add_content(EpubContent::new("chapter_1.xhtml", content.as_bytes())
+    ///                      .title("Chapter 1")
+    ///                      .child(Element::new("chapter_1.xhtml#1", "1.1"))).unwrap();
+    /// ```
+    ///
+    /// ```
+    /// # use epub_builder::{Builder, ZipLibrary, EpubContent};
+    /// # let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// # let content = "Some content";
+    /// // Add a section, by setting the level to 2 (instead of the default value 1)
+    /// builder.add_content(EpubContent::new("section.xhtml", content.as_bytes())
+    ///                      .title("Section 1")
+    ///                      .level(2)).unwrap();
+    /// ```
+    ///
+    /// Note that these files will automatically be inserted into the root directory of
+    /// the package (`OEBPS`, unless set with `root_dir`), so you don't need (and
+    /// shouldn't) prefix your path with `OEBPS/`.
+    ///
+    /// # See also
+    ///
+    /// * [`EpubContent`](struct.EpubContent.html)
+    /// * the `add_resource` method, to add other resources in the EPUB file.
+    ///
+    /// # Errors
+    pub fn add_content<R: Read>(&mut self, mut content: EpubContent<R>) -> Result<&mut Self> {
+        let footnotes: Vec<String> = self
+            .notes
+            .iter()
+            .filter(|n| self.note_style == NoteStyle::Footnotes && n.chapter == content.toc.url)
+            .map(|n| n.render("", "footnote", self.version > Version::V20))
+            .collect();
+        let mut file = Content::new(content.toc.url.as_str(), "application/xhtml+xml");
+        let path = self.layout.path(&self.layout.place(&file.file, &file.mime));
+        // Documents are only read whole if they have to be transformed; otherwise, they
+        // are copied as they are, and their ids collected along the way
+        let ids = if !footnotes.is_empty() || self.auto_toc.is_some() || self.kepub {
+            let mut xhtml = String::new();
+            content
+                .content
+                .read_to_string(&mut xhtml)
+                .wrap_err_with(|| format!("could not read '{}'", content.toc.url))?;
+            if let Some(ref levels) = self.auto_toc {
+                let (output, found) = headings::extract(&xhtml, levels);
+                xhtml = output;
+                content.toc = headings::to_element(content.toc, &found);
+            }
+            if !footnotes.is_empty() {
+                let footnotes = footnotes.join("\n");
+                match xhtml.rfind("</body>") {
+                    Some(pos) => xhtml.insert_str(pos, &format!("{footnotes}\n")),
+                    None => xhtml.push_str(&footnotes),
+                }
+            }
+            if self.kepub {
+                xhtml = kepub::transform(&xhtml);
+            }
+            self.zip.write_file(path, xhtml.as_bytes())?;
+            common::ids(&xhtml)
+        } else {
+            let mut reader = common::IdReader::new(content.content);
+            self.zip.write_file(path, &mut reader)?;
+            reader.into_ids()
+        };
+        file.itemref = true;
+        file.reftype = content.reftype;
+        file.overlay = content.media_overlay;
+        file.title.clone_from(&content.toc.title);
+        file.ids = Some(ids);
+        self.files.push(file);
+        if !content.toc.title.is_empty() {
+            self.toc.add(content.toc);
+        }
+        Ok(self)
+    }
+
+    /// Checks that the targets of the table of contents, of the page list, of the
+    /// navigation lists and of the landmarks added with `add_landmark` point to files
+    /// added with `add_content` or `add_resource`, and that their fragments match the
+    /// `id` of an element of the document. This is done for every rendition.
+    ///
+    /// Fragments can only be checked for documents added with `add_content`. The
+    /// guide and the other landmarks are built from the added files, so they are
+    /// always valid.
+    ///
+    /// This is not done by `generate`, so that it is possible to build a book
+    /// with (not yet) valid targets.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error listing all the broken targets.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use epub_builder::{Builder, Element, EpubContent, ZipLibrary};
+    ///
+    /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// builder.add_content(EpubContent::new("chapter_1.xhtml",
+    ///         "<h2 id=\"section1\">Section 1</h2>".as_bytes())
+    ///         .title("Chapter 1")
+    ///         .child(Element::new("chapter_1.xhtml#sec1", "Section 1")))
+    ///     .unwrap();
+    /// assert!(builder.check_targets().is_err());
+    /// ```
+    pub fn check_targets(&self) -> Result<()> {
+        let errors: Vec<String> = if self.root_files.is_empty() {
+            self.broken_targets()
+        } else {
+            // Tell the renditions apart
+            let current = (self.layout.root.as_str(), self.broken_targets());
+            self.root_files
+                .iter()
+                .map(|r| {
+                    let root = r.path.strip_suffix("/content.opf").unwrap_or(&r.path);
+                    (root, r.broken_targets.clone())
+                })
+                .chain(std::iter::once(current))
+                .flat_map(|(root, errors)| {
+                    errors
+                        .into_iter()
+                        .map(move |error| format!("rendition '{root}': {error}"))
+                })
+                .collect()
+        };
+        if !errors.is_empty() {
+            bail!("broken targets: {}", errors.join("; "));
+        }
+        Ok(())
+    }
+
+    /// Describe the targets of the navigation of the current package that don't match
+    /// an added file, or an element of the document
+    fn broken_targets(&self) -> Vec<String> {
+        let toc = self
+            .toc
+            .iter()
+            .filter(|(_, elem)| elem.is_linked())
+            .map(|(_, elem)| ("toc", elem.url.as_str()));
+        let pages = self
+            .page_list
+            .pages
+            .iter()
+            .map(|page| ("page list", page.href.as_str()));
+        let nav_lists = self.nav_lists.iter().flat_map(|list| {
+            list.targets
+                .iter()
+                .map(|target| (list.kind.epub_type(), target.href.as_str()))
+        });
+        let landmarks = self.landmarks.iter().map(|l| ("landmark", l.href.as_str()));
+        let mut errors: Vec<String> = Vec::new();
+        for (kind, href) in toc.chain(pages).chain(nav_lists).chain(landmarks) {
+            let (document, fragment) = href
+                .split_once('#')
+                .map_or((href, None), |(d, f)| (d, Some(f)));
+            let Some(file) = self.files.iter().find(|f| f.file == document) else {
+                errors.push(format!(
+                    "{kind} target '{href}': '{document}' was not added"
+                ));
+                continue;
+            };
+            if let (Some(fragment), Some(ids)) = (fragment, &file.ids) {
+                if !ids.contains_key(fragment) {
+                    errors.push(format!(
+                        "{kind} target '{href}': no element with id '{fragment}' in '{document}'"
+                    ));
+                }
+            }
+        }
+        errors
+    }
+
+    /// Generate the EPUB file and write it to the writer
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use epub_builder::{Builder, ZipLibrary};
+    /// let mut builder = Builder::new(ZipLibrary::new().unwrap()).unwrap();
+    /// // Write the EPUB file into a Vec<u8>
+    /// let mut epub: Vec<u8> = vec!();
     /// builder.generate(&mut epub).unwrap();
     /// ```
     ///
     /// # Errors
+    ///
+    /// Fails for zip implementations writing the EPUB into a destination of their own,
+    /// such as `ZipStream`: use `finish` instead. Also fails if the EPUB was already
+    /// generated.
     pub fn generate<W: io::Write>(&mut self, to: W) -> Result<()> {
+        if self.zip.is_streamed() {
+            bail!("the EPUB is written into the destination of the zip: use `finish`");
+        }
+        self.write_publication()?;
+        self.zip.generate(to)?;
+        Ok(())
+    }
+
+    /// Generate the EPUB file, for zip implementations writing it into a destination
+    /// of their own as files are added, such as [`ZipStream`](struct.ZipStream.html)
+    ///
+    /// # Errors
+    ///
+    /// Fails for the other zip implementations, which need a writer: use `generate`
+    /// instead. Also fails if the EPUB was already finished.
+    pub fn finish(&mut self) -> Result<()> {
+        if !self.zip.is_streamed() {
+            bail!("the EPUB needs a writer: use `generate`");
+        }
+        self.write_publication()?;
+        self.zip.finish()
+    }
+
+    /// Write the files of the publication which are not written yet: the current
+    /// package, and the files of the container
+    ///
+    /// This can only be done once, even if it fails, since files may have been written.
+    fn write_publication(&mut self) -> Result<()> {
+        if self.generated {
+            bail!("the EPUB was already generated");
+        }
+        self.generated = true;
+        // Write the files of the current package
+        self.write_package()?;
+        self.finish_package();
+        // Write the Apple display options
+        if self
+            .write_apple_display_options
+            .unwrap_or_else(|| self.profile.apple_display_options())
+        {
+            let data = MapBuilder::new()
+                .insert_str(
+                    "options",
+                    common::indent(self.apple_display_options.render(), 2),
+                )
+                .build();
+            let mut bytes = vec![];
+            templates::IBOOKS
+                .render_data(&mut bytes, &data)
+                .wrap_err("error rendering com.apple.ibooks.display-options.xml template")?;
+            self.zip.write_file(
+                "META-INF/com.apple.ibooks.display-options.xml",
+                bytes.as_slice(),
+            )?;
+        }
+        // Render container.xml, and the rendition mapping document
+        self.write_container()?;
+        // Render encryption.xml, for the fonts of all the packages
+        if !self.encryption.is_empty() {
+            self.write_encryption()?;
+        }
+        Ok(())
+    }
+
+    /// Write the files of the current package: documents, content.opf, toc.ncx,
+    /// nav.xhtml and the other generated files
+    fn write_package(&mut self) -> Result<()> {
         // If no styleesheet was provided, generate a dummy one
         if !self.stylesheet {
             self.stylesheet(b"".as_ref())?;
         }
+        if self.version == Version::V33 {
+            for property in self.missing_accessibility_metadata() {
+                log::warn!("EPUB 3.3 publication without '{property}' accessibility metadata");
+            }
+        }
+        // Set the position and title of the inline toc, now that they are known
+        self.place_inline_toc()?;
+        // Same for the titles of inline lists of illustrations and tables
+        self.title_nav_lists();
+        // Write the tables of contents of parts, so they are added to the manifest
+        self.write_mini_tocs()?;
+        // Notes whose chapter is missing would be silently dropped
+        if let Some(note) = self
+            .notes
+            .iter()
+            .find(|n| !self.files.iter().any(|f| f.file == n.chapter))
+        {
+            bail!(
+                "note {} refers to missing chapter '{}'",
+                note.id,
+                note.chapter
+            );
+        }
+        // Write the endnotes document, so it is added to the manifest and the toc
+        if self.note_style == NoteStyle::Endnotes && !self.notes.is_empty() {
+            self.write_endnotes()?;
+        }
         // Render content.opf
         let bytes = self.render_opf()?;
-        self.zip.write_file("OEBPS/content.opf", &*bytes)?;
+        self.zip
+            .write_file(self.layout.path("content.opf"), &*bytes)?;
         // Render toc.ncx
-        let bytes = self.render_toc()?;
-        self.zip.write_file("OEBPS/toc.ncx", &*bytes)?;
-        // Render nav.xhtml
-        let bytes = self.render_nav(true)?;
-        self.zip.write_file("OEBPS/nav.xhtml", &*bytes)?;
+        if self.has_legacy_navigation() {
+            let bytes = self.render_toc()?;
+            self.zip.write_file(self.layout.path("toc.ncx"), &*bytes)?;
+        }
+        // Render nav.xhtml, unless one was provided
+        let bytes = match self.nav_document {
+            Some(ref nav) => {
+                if self.version > Version::V20 {
+                    nav_document::validate(nav)?;
+                }
+                nav.as_bytes().to_vec()
+            }
+            None => self.render_nav()?,
+        };
+        self.zip
+            .write_file(self.layout.path("nav.xhtml"), &*bytes)?;
         // Write inline toc if it needs to
-        if self.inline_toc {
-            let bytes = self.render_nav(false)?;
-            self.zip.write_file("OEBPS/toc.xhtml", &*bytes)?;
+        if let Some(ref options) = self.inline_toc {
+            let bytes = self.render_inline_toc(options)?;
+            self.zip
+                .write_file(self.layout.path(&self.placed("toc.xhtml")), &*bytes)?;
         }
+        // Write inline lists of illustrations and tables
+        for list in &self.nav_lists {
+            if list.inline {
+                let mut placed = list.clone();
+                placed.map_hrefs(&|href| self.placed(href));
+                let content = common::indent(placed.render_list(&self.placed(list.kind.file())), 2);
+                let bytes = self.render_page(
+                    list.kind.file(),
+                    list.kind.epub_type(),
+                    list.kind.epub_type(),
+                    &list.title,
+                    &content,
+                )?;
+                self.zip
+                    .write_file(self.layout.path(&self.placed(list.kind.file())), &*bytes)?;
+            }
+        }
+        // Write SMIL documents for media overlays
+        if self.version >= Version::V30 {
+            self.write_overlays()?;
+        }
+        Ok(())
+    }
 
-        self.zip.generate(to)?;
+    /// Record the current package, once it is written, as a rootfile of the container
+    fn finish_package(&mut self) {
+        self.root_files.push(RootFile {
+            path: format!("{}/content.opf", self.layout.root),
+            rendition: self.rendition.clone(),
+            spine_len: self.files.iter().filter(|f| f.itemref).count(),
+            broken_targets: self.broken_targets(),
+        });
+    }
+
+    /// Clear the contents of the current package, to start a new rendition
+    fn reset_package(&mut self) {
+        self.files.clear();
+        self.toc.elements.clear();
+        self.stylesheet = false;
+        self.inline_toc = None;
+        self.mini_tocs.clear();
+        self.notes.clear();
+        self.page_list = PageList::new();
+        for list in &mut self.nav_lists {
+            list.targets.clear();
+            list.inline = false;
+        }
+        self.landmarks.clear();
+        self.nav_document = None;
+    }
+
+    /// Render container.xml, with a rootfile for each package, and the rendition
+    /// mapping document if needed
+    fn write_container(&mut self) -> Result<()> {
+        let rootfiles: Vec<String> = self
+            .root_files
+            .iter()
+            .map(|r| {
+                format!(
+                    "<rootfile full-path=\"{path}\" media-type=\"application/oebps-package+xml\"{attributes} />",
+                    path = common::escape_quote(r.path.as_str()),
+                    attributes = r.rendition.render_attributes()
+                )
+            })
+            .collect();
+        let links = if self.rendition_mapping {
+            let mapping = rendition::render_mapping(&self.root_files);
+            self.zip.write_file(MAPPING_FILE, mapping.as_bytes())?;
+            format!(
+                "\n  <links>\n    <link href=\"{MAPPING_FILE}\" rel=\"mapping\" \
+                 media-type=\"application/xhtml+xml\" />\n  </links>"
+            )
+        } else {
+            String::new()
+        };
+        let data = MapBuilder::new()
+            .insert_bool(
+                "renditions",
+                rootfiles.iter().any(|r| r.contains("rendition:")) || !links.is_empty(),
+            )
+            .insert_str("rootfiles", common::indent(rootfiles.join("\n"), 2))
+            .insert_str("links", links)
+            .build();
+        let mut bytes = vec![];
+        templates::CONTAINER
+            .render_data(&mut bytes, &data)
+            .wrap_err("error rendering container.xml template")?;
+        self.zip
+            .write_file("META-INF/container.xml", bytes.as_slice())?;
         Ok(())
     }
 
     /// Render content.opf file
     ///
     /// # Errors
-    fn render_opf(&mut self) -> Result<Vec<u8>> {
+    fn render_opf(&self) -> Result<Vec<u8>> {
         log::debug!("render_opf...");
         let mut optional: Vec<String> = Vec::new();
         for desc in &self.metadata.description {
@@ -434,12 +1622,30 @@ impl<Z: Zip> Builder<Z> {
         if let Some(ref rights) = self.metadata.license {
             optional.push(format!("<dc:rights>{rights}</dc:rights>"));
         }
+        if let Some(ref source) = self.metadata.page_break_source {
+            let source = html_escape::encode_text(source);
+            if self.version > Version::V20 {
+                optional.push(format!(
+                    "<dc:source id=\"pagebreak-source\">{source}</dc:source>"
+                ));
+                optional.push(
+                    "<meta refines=\"#pagebreak-source\" property=\"source-of\">pagination</meta>"
+                        .to_string(),
+                );
+                optional.push(format!(
+                    "<meta property=\"a11y:pageBreakSource\">{source}</meta>"
+                ));
+            } else {
+                optional.push(format!("<dc:source>{source}</dc:source>"));
+            }
+        }
         let date = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
-        let uuid = uuid::fmt::Urn::from_uuid(uuid::Uuid::new_v4()).to_string();
+        let uuid = self.identifier();
 
         let mut items: Vec<String> = Vec::new();
         let mut itemrefs: Vec<String> = Vec::new();
         let mut guide: Vec<String> = Vec::new();
+        let mut overlays_duration = std::time::Duration::ZERO;
 
         for content in &self.files {
             let id = if content.cover {
@@ -448,63 +1654,119 @@ impl<Z: Zip> Builder<Z> {
                 to_id(&content.file)
             };
             let properties = match (self.version, content.cover) {
-                (Version::V30, true) => "properties=\"cover-image\" ",
+                (Version::V30 | Version::V33, true) => "properties=\"cover-image\" ",
                 _ => "",
             };
             if content.cover {
-                optional.push("<meta name=\"cover\" content=\"cover-image\"/>".to_string());
+                let meta = "<meta name=\"cover\" content=\"cover-image\"/>".to_string();
+                if self.profile.cover_meta_first() {
+                    optional.insert(0, meta);
+                } else {
+                    optional.push(meta);
+                }
             }
+            let overlay = match content.overlay {
+                Some(ref overlay) if self.version >= Version::V30 => {
+                    let smil = overlay_file(&content.file);
+                    let smil_id = to_id(&smil);
+                    items.push(format!(
+                        "<item media-type=\"application/smil+xml\" id=\"{smil_id}\" href=\"{smil}\"/>"
+                    ));
+                    optional.push(format!(
+                        "<meta property=\"media:duration\" refines=\"#{smil_id}\">{duration}</meta>",
+                        duration = media_overlay::clock_value(overlay.duration())
+                    ));
+                    overlays_duration += overlay.duration();
+                    format!("media-overlay=\"{smil_id}\" ")
+                }
+                _ => String::new(),
+            };
             log::debug!("id={:?}, mime={:?}", id, content.mime);
             items.push(format!(
-                "<item media-type=\"{mime}\" {properties}\
+                "<item media-type=\"{mime}\" {properties}{overlay}\
                         id=\"{id}\" href=\"{href}\"/>",
                 properties = properties,
+                overlay = overlay,
                 mime = content.mime,
                 id = id,
-                // in the zip the path is always with forward slashes, on windows it is with backslashes
-                href = content.file.replace('\\', "/")
+                href = self.layout.place(&content.file, &content.mime)
             ));
             if content.itemref {
                 itemrefs.push(format!("<itemref idref=\"{id}\"/>"));
             }
             if let Some(reftype) = content.reftype {
-                use crate::ReferenceType::{
-                    Acknowledgements, Bibliography, Colophon, Copyright, Cover, Dedication,
-                    Epigraph, Foreword, Glossary, Index, Loi, Lot, Notes, Preface, Text, TitlePage,
-                    Toc,
-                };
-                let reftype = match reftype {
-                    Cover => "cover",
-                    TitlePage => "title-page",
-                    Toc => "toc",
-                    Index => "index",
-                    Glossary => "glossary",
-                    Acknowledgements => "acknowledgements",
-                    Bibliography => "bibliography",
-                    Colophon => "colophon",
-                    Copyright => "copyright",
-                    Dedication => "dedication",
-                    Epigraph => "epigraph",
-                    Foreword => "foreword",
-                    Loi => "loi",
-                    Lot => "lot",
-                    Notes => "notes",
-                    Preface => "preface",
-                    Text => "text",
-                };
                 log::debug!("content = {:?}", &content);
+                // Some reading systems, e.g. Kindle, need a label for each reference
+                let title = if content.title.is_empty() {
+                    reftype.default_title()
+                } else {
+                    content.title.as_str()
+                };
                 guide.push(format!(
                     "<reference type=\"{reftype}\" title=\"{title}\" href=\"{href}\"/>",
-                    reftype = reftype,
+                    reftype = reftype.guide_type(),
                     // escape < > symbols by &lt; &gt; using 'encode_text()' in Title
-                    title = common::escape_quote(html_escape::encode_text(content.title.as_str())),
-                    href = content.file
+                    title = common::escape_quote(html_escape::encode_text(title)),
+                    href = self.layout.place(&content.file, &content.mime)
+                ));
+            }
+        }
+
+        if self.profile.guide_start()
+            && !self
+                .files
+                .iter()
+                .any(|f| f.reftype == Some(ReferenceType::Text))
+        {
+            if let Some(start) = self
+                .files
+                .iter()
+                .find(|f| f.itemref && f.reftype.is_none() && !f.generated)
+            {
+                let title = if start.title.is_empty() {
+                    ReferenceType::Text.default_title()
+                } else {
+                    start.title.as_str()
+                };
+                guide.push(format!(
+                    "<reference type=\"text\" title=\"{title}\" href=\"{href}\"/>",
+                    title = common::escape_quote(html_escape::encode_text(title)),
+                    href = self.layout.place(&start.file, &start.mime)
+                ));
+            }
+        }
+
+        if self.version >= Version::V30 && self.files.iter().any(|f| f.overlay.is_some()) {
+            optional.push(format!(
+                "<meta property=\"media:duration\">{duration}</meta>",
+                duration = media_overlay::clock_value(overlays_duration)
+            ));
+            optional.push(format!(
+                "<meta property=\"media:active-class\">{class}</meta>",
+                class = html_escape::encode_text(&self.metadata.media_active_class)
+            ));
+        }
+
+        if let (true, Some(layout)) = (self.version > Version::V20, self.rendition.layout) {
+            optional.push(format!(
+                "<meta property=\"rendition:layout\">{}</meta>",
+                layout.value()
+            ));
+        }
+
+        if self.version > Version::V20 {
+            for (property, value) in &self.accessibility {
+                optional.push(format!(
+                    "<meta property=\"schema:{property}\">{value}</meta>",
+                    property = common::escape_quote(property),
+                    value = html_escape::encode_text(value)
                 ));
             }
         }
 
         let data = MapBuilder::new()
             .insert_str("lang", self.metadata.lang.as_str())
+            .insert_bool("legacy", self.has_legacy_navigation())
             .insert_vec("author", |builder| {
                 let mut builder = builder;
                 for (i, author) in self.metadata.author.iter().enumerate() {
@@ -530,7 +1792,9 @@ impl<Z: Zip> Builder<Z> {
         let mut content = vec![];
         let res = match self.version {
             Version::V20 => templates::v2::CONTENT_OPF.render_data(&mut content, &data),
-            Version::V30 => templates::v3::CONTENT_OPF.render_data(&mut content, &data),
+            Version::V30 | Version::V33 => {
+                templates::v3::CONTENT_OPF.render_data(&mut content, &data)
+            }
         };
 
         res.wrap_err("could not render template for content.opf")?;
@@ -538,15 +1802,428 @@ impl<Z: Zip> Builder<Z> {
         Ok(content)
     }
 
-    /// Render toc.ncx
-    fn render_toc(&mut self) -> Result<Vec<u8>> {
-        let mut nav_points = String::new();
+    /// Path of a file relative to the root directory, given its path as it was added.
+    /// A fragment is kept as is, and unknown files are left unchanged.
+    fn placed(&self, href: &str) -> String {
+        let (file, fragment) = href
+            .split_once('#')
+            .map_or((href, None), |(f, id)| (f, Some(id)));
+        let Some(content) = self.files.iter().find(|c| c.file == file) else {
+            return href.to_owned();
+        };
+        let placed = self.layout.place(&content.file, &content.mime);
+        match fragment {
+            Some(fragment) => format!("{placed}#{fragment}"),
+            None => placed,
+        }
+    }
+
+    /// Href of an added file (possibly with a fragment) from the generated XHTML
+    /// document `from`, both given by their path as added to the builder
+    fn href_from(&self, from: &str, href: &str) -> String {
+        common::relative_href(
+            &self.layout.place(from, "application/xhtml+xml"),
+            &self.placed(href),
+        )
+    }
+
+    /// The toc, with urls relative to the generated XHTML document `from`
+    fn toc_from(&self, from: &str) -> Toc {
+        let mut toc = self.toc.clone();
+        toc.map_urls(&|url| self.href_from(from, url));
+        toc
+    }
+
+    /// Href of the stylesheet, from a generated document whose path relative to the
+    /// root directory is `from`
+    fn stylesheet_href(&self, from: &str) -> String {
+        common::relative_href(from, &self.layout.place("stylesheet.css", "text/css"))
+    }
+
+    /// The toc, page list and navigation lists, with hrefs relative to the root
+    /// directory, for the files that are written there
+    fn navigation_from_root(&self) -> (Cow<'_, Toc>, Cow<'_, PageList>, Cow<'_, [NavList]>) {
+        if !self.layout.organized {
+            return (
+                Cow::Borrowed(&self.toc),
+                Cow::Borrowed(&self.page_list),
+                Cow::Borrowed(&self.nav_lists),
+            );
+        }
+        let placed = |href: &str| self.placed(href);
+        let mut toc = self.toc.clone();
+        toc.map_urls(&placed);
+        let mut page_list = self.page_list.clone();
+        page_list.map_hrefs(&placed);
+        let mut nav_lists = self.nav_lists.clone();
+        for list in &mut nav_lists {
+            list.map_hrefs(&placed);
+        }
+        (
+            Cow::Owned(toc),
+            Cow::Owned(page_list),
+            Cow::Owned(nav_lists),
+        )
+    }
+
+    /// Position of a target (relative to the package root) in reading order: the index
+    /// of its document in the spine, then the position of its fragment in the document
+    fn reading_position(&self, href: &str) -> (usize, usize) {
+        let (document, fragment) = href
+            .split_once('#')
+            .map_or((href, None), |(d, f)| (d, Some(f)));
+        let Some((index, file)) = self
+            .files
+            .iter()
+            .filter(|f| f.itemref)
+            .enumerate()
+            .find(|(_, f)| self.layout.place(&f.file, &f.mime) == document)
+        else {
+            return (usize::MAX, 0);
+        };
+        let offset = match (fragment, &file.ids) {
+            (None, _) => 0,
+            (Some(fragment), Some(ids)) => ids.get(fragment).map_or(usize::MAX, |pos| pos + 1),
+            (Some(_), None) => usize::MAX,
+        };
+        (index, offset)
+    }
+
+    /// The accessibility metadata required by EPUB Accessibility 1.1 that has not been
+    /// set with `add_accessibility_metadata`
+    fn missing_accessibility_metadata(&self) -> Vec<&'static str> {
+        [
+            "accessMode",
+            "accessibilityFeature",
+            "accessibilityHazard",
+            "accessibilitySummary",
+        ]
+        .into_iter()
+        .filter(|property| !self.accessibility.iter().any(|(p, _)| p == property))
+        .collect()
+    }
+
+    /// Whether toc.ncx and the guide are generated
+    fn has_legacy_navigation(&self) -> bool {
+        let setting = self
+            .legacy_navigation
+            .or_else(|| self.profile.legacy_navigation());
+        match self.version {
+            Version::V20 => true,
+            Version::V30 => setting.unwrap_or(true),
+            Version::V33 => setting.unwrap_or(false),
+        }
+    }
+
+    /// The unique identifier of the package, as written in content.opf
+    fn identifier(&self) -> String {
+        uuid::fmt::Urn::from_uuid(self.metadata.uuid).to_string()
+    }
+
+    /// Render META-INF/encryption.xml
+    fn write_encryption(&mut self) -> Result<()> {
+        let data = MapBuilder::new()
+            .insert_str("entries", common::indent(self.encryption.join("\n"), 1))
+            .build();
+        let mut res: Vec<u8> = vec![];
+        templates::ENCRYPTION_XML
+            .render_data(&mut res, &data)
+            .wrap_err("error rendering encryption.xml template")?;
+        self.zip.write_file("META-INF/encryption.xml", &*res)?;
+        Ok(())
+    }
+
+    /// Render and write the endnotes document, and add it to the spine and the toc
+    fn write_endnotes(&mut self) -> Result<()> {
+        let epub3 = self.version > Version::V20;
+        let mut sections: Vec<String> = Vec::new();
+        for file in &self.files {
+            let notes: Vec<String> = self
+                .notes
+                .iter()
+                .filter(|n| n.chapter == file.file)
+                .map(|n| {
+                    n.render(
+                        &self.href_from(notes::ENDNOTES_FILE, &n.chapter),
+                        "endnote",
+                        epub3,
+                    )
+                })
+                .collect();
+            if notes.is_empty() {
+                continue;
+            }
+            if !file.title.is_empty() {
+                sections.push(format!(
+                    "<h2>{}</h2>",
+                    html_escape::encode_text(file.title.as_str())
+                ));
+            }
+            sections.extend(notes);
+        }
+
+        let res = self.render_page(
+            notes::ENDNOTES_FILE,
+            "endnotes",
+            "endnotes",
+            &self.metadata.notes_title,
+            &common::indent(sections.join("\n"), 2),
+        )?;
+        // Not added with `add_content`, so its headings are not added to the toc
+        let mut content = Content::new(notes::ENDNOTES_FILE, "application/xhtml+xml");
+        self.zip.write_file(
+            self.layout
+                .path(&self.layout.place(&content.file, &content.mime)),
+            &*res,
+        )?;
+        content.itemref = true;
+        content.generated = true;
+        content.reftype = Some(ReferenceType::Notes);
+        content.title.clone_from(&self.metadata.notes_title);
+        content.ids = Some(common::ids(&String::from_utf8_lossy(&res)));
+        self.files.push(content);
+        self.toc.add(
+            Element::new(notes::ENDNOTES_FILE, self.metadata.notes_title.as_str()).unnumbered(),
+        );
+        Ok(())
+    }
+
+    /// Render a generated XHTML page, such as the endnotes or a list of illustrations,
+    /// that is written to `file`
+    fn render_page(
+        &self,
+        file: &str,
+        id: &str,
+        epub_type: &str,
+        title: &str,
+        content: &str,
+    ) -> Result<Vec<u8>> {
+        let data = MapBuilder::new()
+            .insert_str("content", content)
+            .insert_str("title", html_escape::encode_text(title))
+            .insert_str("id", id)
+            .insert_str("epub_type", epub_type)
+            .insert_str("generator", self.metadata.generator.as_str())
+            .insert_str("lang", self.metadata.lang.as_str())
+            .insert_str(
+                "stylesheet",
+                self.stylesheet_href(&self.layout.place(file, "application/xhtml+xml")),
+            )
+            .build();
+        let mut res: Vec<u8> = vec![];
+        let template = if self.version > Version::V20 {
+            &templates::v3::PAGE_XHTML
+        } else {
+            &templates::v2::PAGE_XHTML
+        };
+        template
+            .render_data(&mut res, &data)
+            .wrap_err("error rendering page.xhtml template")?;
+        Ok(res)
+    }
+
+    /// Title of the inline toc
+    fn inline_toc_title<'a>(&'a self, options: &'a InlineToc) -> &'a str {
+        options
+            .title
+            .as_deref()
+            .unwrap_or(self.metadata.toc_name.as_str())
+    }
+
+    /// Move the inline toc to its position in the spine and the toc, if it is set, and
+    /// set its title
+    fn place_inline_toc(&mut self) -> Result<()> {
+        let Some(ref options) = self.inline_toc else {
+            return Ok(());
+        };
+        let title = self.inline_toc_title(options).to_owned();
+        if let Some(position) = options.position {
+            if let Some(i) = self.files.iter().position(|f| f.file == "toc.xhtml") {
+                let file = self.files.remove(i);
+                let next = self
+                    .files
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, f)| f.itemref)
+                    .nth(position)
+                    .map(|(i, f)| (i, f.file.clone()));
+                self.files
+                    .insert(next.as_ref().map_or(self.files.len(), |(i, _)| *i), file);
+                if let Some(elem) = self.toc.remove("toc.xhtml") {
+                    match next {
+                        Some((_, url)) if self.toc.find(&url).is_some() => {
+                            self.toc.insert_before(&url, elem)?;
+                        }
+                        _ => {
+                            self.toc.add(elem);
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(elem) = self.toc.find_mut("toc.xhtml") {
+            elem.title.clone_from(&title);
+        }
+        if let Some(file) = self.files.iter_mut().find(|f| f.file == "toc.xhtml") {
+            file.title = title;
+        }
+        Ok(())
+    }
+
+    /// Set the title of the inline lists of illustrations and tables, in the toc and in
+    /// the manifest
+    fn title_nav_lists(&mut self) {
+        for list in &self.nav_lists {
+            if !list.inline {
+                continue;
+            }
+            if let Some(elem) = self.toc.find_mut(list.kind.file()) {
+                elem.title.clone_from(&list.title);
+            }
+            if let Some(file) = self.files.iter_mut().find(|f| f.file == list.kind.file()) {
+                file.title.clone_from(&list.title);
+            }
+        }
+    }
+
+    /// Render and write the tables of contents of parts, and insert them in the spine
+    fn write_mini_tocs(&mut self) -> Result<()> {
+        for root in self.mini_tocs.clone() {
+            let file = mini_toc_file(&root);
+            if self.files.iter().any(|f| f.file == file) {
+                bail!("can't generate the mini toc of '{root}': '{file}' already exists");
+            }
+            let Some(elem) = self.toc.find(&root) else {
+                bail!("can't generate the mini toc of '{root}': it is not in the toc");
+            };
+            let document = root.split('#').next().unwrap_or(&root);
+            let Some(index) = self.files.iter().position(|f| f.file == document) else {
+                bail!("can't generate the mini toc of '{root}': '{document}' was not added");
+            };
+            let content =
+                self.toc_from(&file)
+                    .render_part(Some(&self.href_from(&file, &root)), None, false);
+            let bytes = self.render_page(&file, "toc", "toc", &elem.title, &content)?;
+            let mut content = Content::new(file, "application/xhtml+xml");
+            self.zip.write_file(
+                self.layout
+                    .path(&self.layout.place(&content.file, &content.mime)),
+                &*bytes,
+            )?;
+            content.itemref = true;
+            content.generated = true;
+            self.files.insert(index + 1, content);
+        }
+        Ok(())
+    }
+
+    /// Render the inline toc
+    fn render_inline_toc(&self, options: &InlineToc) -> Result<Vec<u8>> {
+        let title = self.inline_toc_title(options);
+        let root = options
+            .root
+            .as_deref()
+            .map(|root| self.href_from("toc.xhtml", root));
+        let content = self
+            .toc_from("toc.xhtml")
+            .render_part(root.as_deref(), options.depth, false);
+        let Some(ref template) = options.template else {
+            return self.render_page("toc.xhtml", "toc", "toc", title, &content);
+        };
+        let data = MapBuilder::new()
+            .insert_str("content", content)
+            .insert_str("title", html_escape::encode_text(title))
+            .insert_str("generator", self.metadata.generator.as_str())
+            .insert_str("lang", self.metadata.lang.as_str())
+            .insert_str(
+                "stylesheet",
+                self.stylesheet_href(&self.placed("toc.xhtml")),
+            )
+            .build();
+        let mut res: Vec<u8> = vec![];
+        mustache::compile_str(template)
+            .wrap_err("error compiling inline toc template")?
+            .render_data(&mut res, &data)
+            .wrap_err("error rendering inline toc template")?;
+        Ok(res)
+    }
+
+    /// Render and write the SMIL documents of media overlays
+    fn write_overlays(&mut self) -> Result<()> {
+        let mut written: HashSet<String> = HashSet::new();
+        for content in &self.files {
+            if let Some(ref overlay) = content.overlay {
+                // A SMIL document with an empty `seq` is invalid
+                if overlay.clips.is_empty() {
+                    bail!("the media overlay of '{}' has no clips", content.file);
+                }
+                let smil = overlay_file(&content.file);
+                if !written.insert(smil.clone()) || self.files.iter().any(|f| f.file == smil) {
+                    bail!(
+                        "the media overlay of '{}' would overwrite '{smil}'",
+                        content.file
+                    );
+                }
+                // Hrefs in the SMIL document are relative to it
+                let href =
+                    common::relative_href(&smil, &self.layout.place(&content.file, &content.mime));
+                let pars = overlay.render_smil(&href, &|audio| {
+                    common::relative_href(&smil, &self.placed(audio))
+                });
+                let data = MapBuilder::new()
+                    .insert_str("href", common::escape_quote(href.as_str()))
+                    .insert_str("pars", pars)
+                    .build();
+                let mut res: Vec<u8> = vec![];
+                templates::v3::OVERLAY_SMIL
+                    .render_data(&mut res, &data)
+                    .wrap_err("error rendering overlay.smil template")?;
+                self.zip.write_file(self.layout.path(&smil), &*res)?;
+            }
+        }
+        Ok(())
+    }
 
-        nav_points.push_str(&self.toc.render_epub());
+    /// Render toc.ncx
+    fn render_toc(&self) -> Result<Vec<u8>> {
+        // playOrder is shared between the nav map, the page list and the nav lists, and
+        // follows the reading order
+        let (toc, page_list, nav_lists) = self.navigation_from_root();
+        let targets = toc
+            .iter()
+            .map(|(_, elem)| elem.target())
+            .chain(page_list.pages.iter().map(|page| page.href.as_str()))
+            .chain(
+                nav_lists
+                    .iter()
+                    .flat_map(|list| list.targets.iter().map(|t| t.href.as_str())),
+            );
+        let mut play_order =
+            PlayOrder::in_reading_order(targets, &|href| self.reading_position(href));
+        let nav_points = toc.render_ncx(&mut play_order);
+        let page_list = page_list.render_ncx(&mut play_order);
+        let nav_lists = nav_lists
+            .iter()
+            .map(|list| list.render_ncx(&mut play_order))
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
 
         let data = MapBuilder::new()
             .insert_str("toc_name", self.metadata.toc_name.as_str())
+            .insert_str("uid", self.identifier())
+            .insert_str("depth", self.toc.depth().to_string())
             .insert_str("nav_points", nav_points.as_str())
+            .insert_str(
+                "total_page_count",
+                self.page_list.total_page_count().to_string(),
+            )
+            .insert_str(
+                "max_page_number",
+                self.page_list.max_page_number().to_string(),
+            )
+            .insert_str("page_list", page_list)
+            .insert_str("nav_lists", nav_lists)
             .build();
         let mut res: Vec<u8> = vec![];
         templates::TOC_NCX
@@ -556,46 +2233,35 @@ impl<Z: Zip> Builder<Z> {
     }
 
     /// Render nav.xhtml
-    fn render_nav(&mut self, numbered: bool) -> Result<Vec<u8>> {
-        let content = self.toc.render(numbered);
+    fn render_nav(&self) -> Result<Vec<u8>> {
+        let (toc, page_list, nav_lists) = self.navigation_from_root();
+        let content = toc.render_part(None, None, true);
         let mut landmarks: Vec<String> = Vec::new();
         if self.version > Version::V20 {
-            for file in &self.files {
-                if let Some(ref reftype) = file.reftype {
-                    use ReferenceType::{
-                        Acknowledgements, Bibliography, Colophon, Copyright, Cover, Dedication,
-                        Epigraph, Foreword, Glossary, Index, Loi, Lot, Notes, Preface, Text,
-                        TitlePage, Toc,
+            let from_files = self.files.iter().filter_map(|file| {
+                file.reftype.map(|reftype| {
+                    // Landmarks need a label, even if the content doesn't appear in the toc
+                    let title = if file.title.is_empty() {
+                        reftype.default_title()
+                    } else {
+                        file.title.as_str()
                     };
-                    let reftype = match *reftype {
-                        Cover => "cover",
-                        Text => "bodymatter",
-                        Toc => "toc",
-                        Bibliography => "bibliography",
-                        Epigraph => "epigraph",
-                        Foreword => "foreword",
-                        Preface => "preface",
-                        Notes => "endnotes",
-                        Loi => "loi",
-                        Lot => "lot",
-                        Colophon => "colophon",
-                        TitlePage => "titlepage",
-                        Index => "index",
-                        Glossary => "glossary",
-                        Copyright => "copyright-page",
-                        Acknowledgements => "acknowledgements",
-                        Dedication => "dedication",
-                    };
-                    if !file.title.is_empty() {
-                        landmarks.push(format!(
-                            "<li><a epub:type=\"{reftype}\" href=\"{href}\">\
-                                {title}</a></li>",
-                            reftype = reftype,
-                            href = file.file,
-                            title = file.title
-                        ));
-                    }
-                }
+                    let href = self.layout.place(&file.file, &file.mime);
+                    (reftype.landmark_type(), title, href)
+                })
+            });
+            let added = self
+                .landmarks
+                .iter()
+                .map(|l| (l.epub_type.as_str(), l.title.as_str(), self.placed(&l.href)));
+            for (epub_type, title, href) in from_files.chain(added) {
+                landmarks.push(format!(
+                    "<li><a epub:type=\"{epub_type}\" href=\"{href}\">\
+                        {title}</a></li>",
+                    epub_type = common::escape_quote(epub_type),
+                    href = common::escape_quote(href),
+                    title = html_escape::encode_text(title)
+                ));
             }
         }
 
@@ -603,23 +2269,38 @@ impl<Z: Zip> Builder<Z> {
             .insert_str("content", content)
             .insert_str("toc_name", self.metadata.toc_name.as_str())
             .insert_str("generator", self.metadata.generator.as_str())
+            .insert_str("lang", self.metadata.lang.as_str())
+            .insert_str("stylesheet", self.stylesheet_href("nav.xhtml"))
             .insert_str(
                 "landmarks",
                 if landmarks.is_empty() {
                     String::new()
                 } else {
                     common::indent(
-                        format!("<ol>\n{}\n</ol>", common::indent(landmarks.join("\n"), 1)),
-                        2,
+                        format!(
+                            "<nav epub:type=\"landmarks\" id=\"landmarks\">\n  <ol>\n{}\n  </ol>\n</nav>",
+                            common::indent(landmarks.join("\n"), 2)
+                        ),
+                        1,
                     )
                 },
             )
+            .insert_str("page_list", page_list.render_nav())
+            .insert_str(
+                "nav_lists",
+                nav_lists
+                    .iter()
+                    .map(NavList::render_nav)
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )
             .build();
 
         let mut res = vec![];
         let eh = match self.version {
             Version::V20 => templates::v2::NAV_XHTML.render_data(&mut res, &data),
-            Version::V30 => templates::v3::NAV_XHTML.render_data(&mut res, &data),
+            Version::V30 | Version::V33 => templates::v3::NAV_XHTML.render_data(&mut res, &data),
         };
 
         eh.wrap_err("error rendering nav.xhtml template")?;
@@ -658,3 +2339,1121 @@ fn is_id_char(c: char) -> bool {
 fn to_id(s: &str) -> String {
     s.replace(|c: char| !is_id_char(c), "_")
 }
+
+/// Path of the rendition mapping document in the container
+const MAPPING_FILE: &str = "mapping.xhtml";
+
+// name of the SMIL document for the media overlay of a XHTML file, which is written
+// next to it, e.g. `text/ch1.xhtml` gives `text/ch1.smil`
+fn overlay_file(file: &str) -> String {
+    let smil = Path::new(file).with_extension("smil");
+    // in the zip the path is always with forward slashes, on windows it is with backslashes
+    format!("{}", smil.display()).replace('\\', "/")
+}
+
+// name of the table of contents of a part, e.g. `text/part1.xhtml` gives
+// `toc_text_part1.xhtml` and `text/part1.xhtml#a` gives `toc_text_part1_a.xhtml`
+fn mini_toc_file(root: &str) -> String {
+    let (document, fragment) = root
+        .split_once('#')
+        .map_or((root, None), |(d, f)| (d, Some(f)));
+    let stem = Path::new(document).with_extension("");
+    let mut name = to_id(&format!("{}", stem.display()));
+    if let Some(fragment) = fragment {
+        name = format!("{name}_{}", to_id(fragment));
+    }
+    format!("toc_{name}.xhtml")
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+///                                  TESTS                                     //
+/////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_obfuscated_font() {
+    let font: Vec<u8> = (0..=255).cycle().take(2048).collect();
+    let uuid = uuid::Uuid::parse_str("0f4d3b1a-5c2e-4b8f-9a6d-7e1c2b3a4d5e").unwrap();
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .set_uuid(uuid)
+        .unwrap()
+        .add_font(
+            "fonts/a.otf",
+            font.as_slice(),
+            "font/otf",
+            Obfuscation::Idpf,
+        )
+        .unwrap();
+    assert!(builder.set_uuid(uuid::Uuid::nil()).is_err());
+    let mut archive = generate_archive(&mut builder);
+
+    let encryption =
+        String::from_utf8(read_archive_file(&mut archive, "META-INF/encryption.xml")).unwrap();
+    assert!(encryption.contains("Algorithm=\"http://www.idpf.org/2008/embedding\""));
+    assert!(encryption.contains("URI=\"OEBPS/fonts/a.otf\""));
+
+    let mut obfuscated = read_archive_file(&mut archive, "OEBPS/fonts/a.otf");
+    assert_ne!(obfuscated, font);
+    Obfuscation::Idpf
+        .obfuscate(
+            "urn:uuid:0f4d3b1a-5c2e-4b8f-9a6d-7e1c2b3a4d5e",
+            &mut obfuscated,
+        )
+        .unwrap();
+    assert_eq!(obfuscated, font);
+
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    assert!(opf.contains("urn:uuid:0f4d3b1a-5c2e-4b8f-9a6d-7e1c2b3a4d5e"));
+    assert!(opf.contains("href=\"fonts/a.otf\""));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_no_encryption_without_fonts() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    let archive = generate_archive(&mut builder);
+    assert!(!archive.file_names().any(|f| f == "META-INF/encryption.xml"));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_media_overlay() {
+    use std::time::Duration;
+
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .add_resource("audio/ch1.mp3", b"".as_ref(), "audio/mpeg")
+        .unwrap()
+        .add_content(
+            EpubContent::new("text/ch1.xhtml", b"".as_ref()).media_overlay(
+                MediaOverlay::new()
+                    .clip(
+                        "p1",
+                        "audio/ch1.mp3",
+                        Duration::ZERO,
+                        Duration::from_secs(3),
+                    )
+                    .clip(
+                        "p2",
+                        "audio/ch1.mp3",
+                        Duration::from_secs(3),
+                        Duration::from_secs(65),
+                    ),
+            ),
+        )
+        .unwrap();
+    let mut archive = generate_archive(&mut builder);
+
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    assert!(opf.contains(
+        "<item media-type=\"application/smil+xml\" id=\"text_ch1.smil\" href=\"text/ch1.smil\"/>"
+    ));
+    assert!(opf.contains("media-overlay=\"text_ch1.smil\" id=\"text_ch1.xhtml\""));
+    assert!(opf.contains(
+        "<meta property=\"media:duration\" refines=\"#text_ch1.smil\">0:01:05.000</meta>"
+    ));
+    assert!(opf.contains("<meta property=\"media:duration\">0:01:05.000</meta>"));
+    assert!(opf.contains("<meta property=\"media:active-class\">-epub-media-overlay-active</meta>"));
+
+    let smil = String::from_utf8(read_archive_file(&mut archive, "OEBPS/text/ch1.smil")).unwrap();
+    assert!(smil.contains("epub:textref=\"ch1.xhtml\""));
+    assert!(smil.contains("<text src=\"ch1.xhtml#p2\"/>"));
+    assert!(smil.contains(
+        "<audio src=\"../audio/ch1.mp3\" clipBegin=\"0:00:03.000\" clipEnd=\"0:01:05.000\"/>"
+    ));
+
+    // Documents whose SMIL documents would have the same name
+    let overlay = MediaOverlay::new().clip("p1", "ch1.mp3", Duration::ZERO, Duration::from_secs(1));
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .add_content(EpubContent::new("ch1.xhtml", b"".as_ref()).media_overlay(overlay.clone()))
+        .unwrap()
+        .add_content(EpubContent::new("ch1.html", b"".as_ref()).media_overlay(overlay))
+        .unwrap();
+    assert!(builder.generate(&mut vec![]).is_err());
+
+    // Media overlays without clips
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .add_content(EpubContent::new("ch1.xhtml", b"".as_ref()).media_overlay(MediaOverlay::new()))
+        .unwrap();
+    assert!(builder.generate(&mut vec![]).is_err());
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_media_overlay_epub2() {
+    use std::time::Duration;
+
+    let overlay = MediaOverlay::new().clip("p1", "ch1.mp3", Duration::ZERO, Duration::from_secs(1));
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .add_content(EpubContent::new("ch1.xhtml", b"".as_ref()).media_overlay(overlay))
+        .unwrap();
+    let mut archive = generate_archive(&mut builder);
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    assert!(!opf.contains("media-overlay"));
+    assert!(!opf.contains("media:"));
+    assert!(!archive.file_names().any(|f| f == "OEBPS/ch1.smil"));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_footnotes() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder.epub_version(Version::V30);
+    let noteref = builder.add_note("ch1.xhtml", "<p>Note</p>").unwrap();
+    assert_eq!(
+        noteref,
+        "<sup><a epub:type=\"noteref\" class=\"noteref\" href=\"#note-1\" id=\"noteref-1\">1</a></sup>"
+    );
+    let chapter = format!("<html><body><p>Text{noteref}</p></body></html>");
+    builder
+        .add_content(EpubContent::new("ch1.xhtml", chapter.as_bytes()))
+        .unwrap();
+    assert!(builder.add_note("ch1.xhtml", "Too late").is_err());
+    let mut archive = generate_archive(&mut builder);
+
+    let chapter = String::from_utf8(read_archive_file(&mut archive, "OEBPS/ch1.xhtml")).unwrap();
+    assert_eq!(
+        chapter,
+        "<html><body><p>Text<sup><a epub:type=\"noteref\" class=\"noteref\" href=\"#note-1\" id=\"noteref-1\">1</a></sup></p>\
+<aside epub:type=\"footnote\" id=\"note-1\">
+  <a class=\"note-backlink\" href=\"#noteref-1\">1.</a>
+  <p>Note</p>
+</aside>
+</body></html>"
+    );
+    assert!(!archive.file_names().any(|f| f == "OEBPS/endnotes.xhtml"));
+
+    // A note for a chapter that is never added
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder.add_note("missing.xhtml", "Lost").unwrap();
+    assert!(builder.generate(&mut vec![]).is_err());
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_endnotes() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder.note_style(NoteStyle::Endnotes).auto_toc(1..=2);
+    let first = builder.add_note("text/ch1.xhtml", "First").unwrap();
+    builder.add_note("text/ch2.xhtml", "Second").unwrap();
+    let third = builder.add_note("text/ch1.xhtml", "Third").unwrap();
+    assert_eq!(
+        first,
+        "<sup><a class=\"noteref\" href=\"../endnotes.xhtml#note-1\" id=\"noteref-1\">1</a></sup>"
+    );
+    assert_eq!(
+        third,
+        "<sup><a class=\"noteref\" href=\"../endnotes.xhtml#note-3\" id=\"noteref-3\">2</a></sup>"
+    );
+    builder
+        .add_content(EpubContent::new("text/ch1.xhtml", b"".as_ref()).title("Chapter 1"))
+        .unwrap()
+        .add_content(EpubContent::new("text/ch2.xhtml", b"".as_ref()).title("Chapter 2"))
+        .unwrap();
+    let mut archive = generate_archive(&mut builder);
+
+    let endnotes =
+        String::from_utf8(read_archive_file(&mut archive, "OEBPS/endnotes.xhtml")).unwrap();
+    assert!(endnotes.contains(
+        "    <h2>Chapter 1</h2>
+    <div class=\"endnote\" id=\"note-1\">
+      <a class=\"note-backlink\" href=\"text/ch1.xhtml#noteref-1\">1.</a>
+      First
+    </div>
+    <div class=\"endnote\" id=\"note-3\">
+      <a class=\"note-backlink\" href=\"text/ch1.xhtml#noteref-3\">2.</a>
+      Third
+    </div>
+    <h2>Chapter 2</h2>"
+    ));
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    assert!(opf.contains("<itemref idref=\"endnotes.xhtml\"/>"));
+    assert!(opf.contains("<reference type=\"notes\" title=\"Notes\" href=\"endnotes.xhtml\"/>"));
+    let ncx = String::from_utf8(read_archive_file(&mut archive, "OEBPS/toc.ncx")).unwrap();
+    assert!(ncx.contains("<content src=\"endnotes.xhtml\"/>"));
+    // The headings of the endnotes are not in the toc
+    assert!(!ncx.contains("endnotes.xhtml#"));
+    let endnotes =
+        String::from_utf8(read_archive_file(&mut archive, "OEBPS/endnotes.xhtml")).unwrap();
+    assert!(!endnotes.contains("heading-"));
+
+    // A note for a chapter that is never added
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder.note_style(NoteStyle::Endnotes);
+    builder.add_note("missing.xhtml", "Lost").unwrap();
+    assert!(builder.generate(&mut vec![]).is_err());
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_page_list() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .page_break_source("urn:isbn:9780000000000")
+        .add_page_break("1", "ch1.xhtml#page1")
+        .add_page_break("2", "ch1.xhtml#page2");
+    let mut archive = generate_archive(&mut builder);
+
+    let nav = String::from_utf8(read_archive_file(&mut archive, "OEBPS/nav.xhtml")).unwrap();
+    assert!(nav.contains(
+        "  <nav epub:type=\"page-list\" hidden=\"hidden\">
+    <ol>
+      <li><a href=\"ch1.xhtml#page1\">1</a></li>
+      <li><a href=\"ch1.xhtml#page2\">2</a></li>
+    </ol>
+  </nav>"
+    ));
+    let ncx = String::from_utf8(read_archive_file(&mut archive, "OEBPS/toc.ncx")).unwrap();
+    assert!(ncx.contains("<meta name=\"dtb:totalPageCount\" content=\"2\" />"));
+    assert!(ncx.contains("<meta name=\"dtb:maxPageNumber\" content=\"2\" />"));
+    assert!(ncx
+        .contains("<pageTarget id=\"pageTarget-2\" type=\"normal\" value=\"2\" playOrder=\"2\">"));
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    assert!(opf.contains("<dc:source id=\"pagebreak-source\">urn:isbn:9780000000000</dc:source>"));
+    assert!(opf.contains("<meta property=\"a11y:pageBreakSource\">urn:isbn:9780000000000</meta>"));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_nav_lists() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .nav_list_title(NavListKind::Illustrations, "Figures")
+        .inline_nav_list(NavListKind::Illustrations)
+        .add_to_nav_list(
+            NavListKind::Illustrations,
+            "Figure 1",
+            "text/ch1.xhtml#fig1",
+        )
+        .add_to_nav_list(NavListKind::Tables, "Table 1", "text/ch1.xhtml#table1");
+    let mut archive = generate_archive(&mut builder);
+
+    let nav = String::from_utf8(read_archive_file(&mut archive, "OEBPS/nav.xhtml")).unwrap();
+    assert!(nav.contains("<nav epub:type=\"loi\" id=\"loi\">\n    <h2>Figures</h2>"));
+    assert!(nav.contains("<nav epub:type=\"lot\" id=\"lot\">"));
+    let ncx = String::from_utf8(read_archive_file(&mut archive, "OEBPS/toc.ncx")).unwrap();
+    assert!(ncx.contains("<navTarget id=\"navTarget-loi-1\" playOrder=\"2\">"));
+    assert!(ncx.contains("<navTarget id=\"navTarget-lot-1\" playOrder=\"3\">"));
+    let loi = String::from_utf8(read_archive_file(&mut archive, "OEBPS/loi.xhtml")).unwrap();
+    assert!(loi.contains("<section epub:type=\"loi\" id=\"loi\">"));
+    assert!(loi.contains("<li><a href=\"text/ch1.xhtml#fig1\">Figure 1</a></li>"));
+    assert!(!archive.file_names().any(|f| f == "OEBPS/lot.xhtml"));
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    assert!(opf.contains("<itemref idref=\"loi.xhtml\"/>"));
+
+    // The title of inline lists is the one they have when the EPUB is generated
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .inline_nav_list(NavListKind::Tables)
+        .add_to_nav_list(NavListKind::Tables, "Table 1", "lot.xhtml")
+        .nav_list_title(NavListKind::Tables, "Tables");
+    let mut archive = generate_archive(&mut builder);
+    let nav = String::from_utf8(read_archive_file(&mut archive, "OEBPS/nav.xhtml")).unwrap();
+    assert!(nav.contains("<li><a href=\"lot.xhtml\">Tables</a></li>"));
+    assert!(nav.contains("<li><a epub:type=\"lot\" href=\"lot.xhtml\">Tables</a></li>"));
+    let ncx = String::from_utf8(read_archive_file(&mut archive, "OEBPS/toc.ncx")).unwrap();
+    assert!(!ncx.contains("List of Tables"));
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    assert!(opf.contains("<reference type=\"lot\" title=\"Tables\" href=\"lot.xhtml\"/>"));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_landmarks() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .add_content(EpubContent::new("cover.xhtml", b"".as_ref()).reftype(ReferenceType::Cover))
+        .unwrap()
+        .add_landmark("bodymatter", "Start of <text>", "front.xhtml#start");
+    let mut archive = generate_archive(&mut builder);
+
+    let nav = String::from_utf8(read_archive_file(&mut archive, "OEBPS/nav.xhtml")).unwrap();
+    assert!(nav.contains(
+        "    <ol>
+      <li><a epub:type=\"cover\" href=\"cover.xhtml\">Cover</a></li>
+      <li><a epub:type=\"bodymatter\" href=\"front.xhtml#start\">Start of &lt;text&gt;</a></li>
+    </ol>"
+    ));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_nav_document() {
+    let nav = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<body><nav epub:type="toc"><ol><li><a href="ch1.xhtml">Custom</a></li></ol></nav></body>
+</html>"#;
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .nav_document(nav.as_bytes())
+        .unwrap()
+        .add_content(EpubContent::new("ch1.xhtml", b"".as_ref()).title("Chapter 1"))
+        .unwrap();
+    let mut archive = generate_archive(&mut builder);
+    assert_eq!(
+        read_archive_file(&mut archive, "OEBPS/nav.xhtml"),
+        nav.as_bytes()
+    );
+    let ncx = String::from_utf8(read_archive_file(&mut archive, "OEBPS/toc.ncx")).unwrap();
+    assert!(ncx.contains("<text>Chapter 1</text>"));
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    assert!(opf.contains("properties=\"nav\""));
+
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .nav_document(b"<html></html>".as_ref())
+        .unwrap();
+    assert!(builder.generate(&mut vec![]).is_err());
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_ncx_play_order() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .add_page_break("2", "ch1.xhtml#p2")
+        .add_page_break("1", "ch1.xhtml#p1")
+        .add_page_break("3", "ch2.xhtml#p3")
+        .add_to_nav_list(NavListKind::Illustrations, "Figure 1", "ch1.xhtml#fig1")
+        .add_content(
+            EpubContent::new(
+                "ch1.xhtml",
+                b"<p id=\"p1\"/><img id=\"fig1\"/><p id=\"p2\"/>".as_ref(),
+            )
+            .title("Chapter 1"),
+        )
+        .unwrap()
+        .add_content(EpubContent::new("ch2.xhtml", b"<p id=\"p3\"/>".as_ref()).title("Chapter 2"))
+        .unwrap();
+    let mut archive = generate_archive(&mut builder);
+
+    let ncx = String::from_utf8(read_archive_file(&mut archive, "OEBPS/toc.ncx")).unwrap();
+    assert!(ncx.contains("<navPoint id=\"navPoint-1\" playOrder=\"1\">"));
+    assert!(ncx
+        .contains("<pageTarget id=\"pageTarget-2\" type=\"normal\" value=\"1\" playOrder=\"2\">"));
+    assert!(ncx.contains("<navTarget id=\"navTarget-loi-1\" playOrder=\"3\">"));
+    assert!(ncx
+        .contains("<pageTarget id=\"pageTarget-1\" type=\"normal\" value=\"2\" playOrder=\"4\">"));
+    assert!(ncx.contains("<navPoint id=\"navPoint-2\" playOrder=\"5\">"));
+    assert!(ncx
+        .contains("<pageTarget id=\"pageTarget-3\" type=\"normal\" value=\"3\" playOrder=\"6\">"));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_toc_depth() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .set_uuid(uuid::Uuid::nil())
+        .unwrap()
+        .toc_depth(2)
+        .add_content(
+            EpubContent::new("ch1.xhtml", b"".as_ref())
+                .title("Chapter 1")
+                .child(
+                    Element::new("ch1.xhtml#s1", "Section 1")
+                        .child(Element::new("ch1.xhtml#s1.1", "Section 1.1")),
+                ),
+        )
+        .unwrap();
+    let mut archive = generate_archive(&mut builder);
+
+    let ncx = String::from_utf8(read_archive_file(&mut archive, "OEBPS/toc.ncx")).unwrap();
+    assert!(ncx.contains(
+        "<meta name=\"dtb:uid\" content=\"urn:uuid:00000000-0000-0000-0000-000000000000\" />"
+    ));
+    assert!(ncx.contains("<meta name=\"dtb:depth\" content=\"2\" />"));
+    assert!(ncx.contains("<navPoint id=\"navPoint-2\" playOrder=\"2\">"));
+    assert!(!ncx.contains("Section 1.1"));
+    let nav = String::from_utf8(read_archive_file(&mut archive, "OEBPS/nav.xhtml")).unwrap();
+    assert!(nav.contains("Section 1"));
+    assert!(!nav.contains("Section 1.1"));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_auto_toc() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .auto_toc(1..=2)
+        .add_content(EpubContent::new(
+            "ch1.xhtml",
+            b"<body><h1>Chapter 1</h1><h2 id=\"s1\">Section 1</h2><h3>Deep</h3></body>".as_ref(),
+        ))
+        .unwrap()
+        .add_content(
+            EpubContent::new("ch2.xhtml", b"<body><h1>Detected</h1></body>".as_ref())
+                .title("Chapter 2"),
+        )
+        .unwrap();
+    let mut archive = generate_archive(&mut builder);
+
+    let ch1 = String::from_utf8(read_archive_file(&mut archive, "OEBPS/ch1.xhtml")).unwrap();
+    assert!(ch1.contains("<h1 id=\"heading-1\">Chapter 1</h1>"));
+    assert!(ch1.contains("<h3>Deep</h3>"));
+    let nav = String::from_utf8(read_archive_file(&mut archive, "OEBPS/nav.xhtml")).unwrap();
+    assert!(nav.contains("<a href=\"ch1.xhtml\">Chapter 1</a>"));
+    assert!(nav.contains("<a href=\"ch1.xhtml#s1\">Section 1</a>"));
+    assert!(!nav.contains("Deep"));
+    assert!(nav.contains("<a href=\"ch2.xhtml\">Chapter 2</a>"));
+    assert!(!nav.contains("Detected"));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_toc_numbering() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .toc_numbering(Numbering::Decimal)
+        .inline_toc()
+        .add_content(
+            EpubContent::new("preface.xhtml", b"".as_ref())
+                .title("Preface")
+                .unnumbered(),
+        )
+        .unwrap()
+        .add_content(
+            EpubContent::new("ch1.xhtml", b"".as_ref())
+                .title("Chapter")
+                .child(Element::new("ch1.xhtml#1", "Section")),
+        )
+        .unwrap();
+    let mut archive = generate_archive(&mut builder);
+
+    let nav = String::from_utf8(read_archive_file(&mut archive, "OEBPS/nav.xhtml")).unwrap();
+    // Titles are already numbered, so the list numbers are hidden
+    assert!(nav.contains(
+        "<ol style=\"list-style: none\">
+      <li><a href=\"toc.xhtml\">Table Of Contents</a></li>"
+    ));
+    assert!(nav.contains(">Preface</a>"));
+    assert!(nav.contains(">1 Chapter</a>"));
+    assert!(nav.contains(">1.1 Section</a>"));
+    let toc = String::from_utf8(read_archive_file(&mut archive, "OEBPS/toc.xhtml")).unwrap();
+    assert!(toc.contains(">1.1 Section</a>"));
+    let ncx = String::from_utf8(read_archive_file(&mut archive, "OEBPS/toc.ncx")).unwrap();
+    assert!(ncx.contains("<text>1.1 Section</text>"));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_unlinked_toc_element() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .add_toc_element(Element::unlinked("Part One"))
+        .add_content(
+            EpubContent::new("ch1.xhtml", b"".as_ref())
+                .title("Chapter 1")
+                .level(2),
+        )
+        .unwrap();
+    let mut archive = generate_archive(&mut builder);
+
+    let nav = String::from_utf8(read_archive_file(&mut archive, "OEBPS/nav.xhtml")).unwrap();
+    assert!(nav.contains("<span>Part One</span>"));
+    let ncx = String::from_utf8(read_archive_file(&mut archive, "OEBPS/toc.ncx")).unwrap();
+    assert!(ncx
+        .contains("<text>Part One</text>\n      </navLabel>\n      <content src=\"ch1.xhtml\"/>"));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_title_markup() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .add_content(
+            EpubContent::new("ch1.xhtml", b"".as_ref())
+                .title_markup("H<sub>2</sub>O")
+                .reftype(ReferenceType::Text),
+        )
+        .unwrap();
+    let mut archive = generate_archive(&mut builder);
+
+    let nav = String::from_utf8(read_archive_file(&mut archive, "OEBPS/nav.xhtml")).unwrap();
+    assert!(nav.contains("<a href=\"ch1.xhtml\">H<sub>2</sub>O</a>"));
+    assert!(nav.contains("<a epub:type=\"bodymatter\" href=\"ch1.xhtml\">H2O</a>"));
+    let ncx = String::from_utf8(read_archive_file(&mut archive, "OEBPS/toc.ncx")).unwrap();
+    assert!(ncx.contains("<text>H2O</text>"));
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    assert!(opf.contains("title=\"H2O\""));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_imported_toc() {
+    let ncx = r#"<ncx><navMap>
+  <navPoint><navLabel><text>Chapter 1</text></navLabel><content src="ch1.xhtml"/></navPoint>
+</navMap></ncx>"#;
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .add_toc(Toc::from_ncx(ncx).unwrap())
+        .add_content(EpubContent::new("ch1.xhtml", b"".as_ref()))
+        .unwrap();
+    let mut archive = generate_archive(&mut builder);
+
+    let nav = String::from_utf8(read_archive_file(&mut archive, "OEBPS/nav.xhtml")).unwrap();
+    assert!(nav.contains("<li><a href=\"ch1.xhtml\">Chapter 1</a></li>"));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_inline_toc_options() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .add_content(EpubContent::new("cover.xhtml", b"".as_ref()))
+        .unwrap()
+        .add_content(
+            EpubContent::new("p1.xhtml", b"".as_ref())
+                .title("Part 1")
+                .child(
+                    Element::new("ch1.xhtml", "Chapter 1")
+                        .child(Element::new("ch1.xhtml#1", "Section 1")),
+                ),
+        )
+        .unwrap()
+        .inline_toc_with(
+            InlineToc::new()
+                .position(1)
+                .root("p1.xhtml")
+                .depth(1)
+                .template("<p>{{{title}}}</p>\n{{{content}}}"),
+        )
+        .metadata(MetadataKind::TocName, "Contents");
+    let mut archive = generate_archive(&mut builder);
+
+    let toc = String::from_utf8(read_archive_file(&mut archive, "OEBPS/toc.xhtml")).unwrap();
+    assert!(toc.starts_with("<p>Contents</p>"));
+    assert!(toc.contains("<li><a href=\"ch1.xhtml\">Chapter 1</a></li>"));
+    assert!(!toc.contains("Part 1"));
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    let spine: Vec<_> = opf
+        .match_indices("<itemref idref=")
+        .map(|(i, _)| &opf[i..i + 30])
+        .collect();
+    assert!(spine[0].contains("cover.xhtml"));
+    assert!(spine[1].contains("toc.xhtml"));
+    let nav = String::from_utf8(read_archive_file(&mut archive, "OEBPS/nav.xhtml")).unwrap();
+    let toc_entry = nav.find(">Contents</a>").unwrap();
+    assert!(toc_entry < nav.find(">Part 1</a>").unwrap());
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_mini_toc() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .add_content(EpubContent::new("text/part1.xhtml", b"".as_ref()).title("Part 1"))
+        .unwrap()
+        .add_content(
+            EpubContent::new("text/ch1.xhtml", b"".as_ref())
+                .title("Chapter 1")
+                .level(2),
+        )
+        .unwrap()
+        .add_content(EpubContent::new("text/part2.xhtml", b"".as_ref()).title("Part 2"))
+        .unwrap()
+        .add_mini_toc("text/part1.xhtml");
+    let mut archive = generate_archive(&mut builder);
+
+    let toc = String::from_utf8(read_archive_file(
+        &mut archive,
+        "OEBPS/toc_text_part1.xhtml",
+    ))
+    .unwrap();
+    assert!(toc.contains("<title>Part 1</title>"));
+    assert!(toc.contains("<li><a href=\"text/ch1.xhtml\">Chapter 1</a></li>"));
+    assert!(!toc.contains("Part 2"));
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    let part = opf.find("<itemref idref=\"text_part1.xhtml\"/>").unwrap();
+    let mini = opf
+        .find("<itemref idref=\"toc_text_part1.xhtml\"/>")
+        .unwrap();
+    let chapter = opf.find("<itemref idref=\"text_ch1.xhtml\"/>").unwrap();
+    assert!(part < mini && mini < chapter);
+
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder.add_mini_toc("missing.xhtml");
+    assert!(builder.generate(&mut vec![]).is_err());
+
+    // Parts whose mini tocs would have the same name
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .add_content(EpubContent::new("a/b.xhtml", b"".as_ref()).title("Part 1"))
+        .unwrap()
+        .add_content(EpubContent::new("a_b.xhtml", b"".as_ref()).title("Part 2"))
+        .unwrap()
+        .add_mini_toc("a/b.xhtml")
+        .add_mini_toc("a_b.xhtml");
+    assert!(builder.generate(&mut vec![]).is_err());
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_check_targets() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .add_resource("image.png", b"".as_ref(), "image/png")
+        .unwrap()
+        .add_content(
+            EpubContent::new(
+                "ch1.xhtml",
+                b"<h2 id=\"s1\">1</h2><h2 id='s2'>2</h2>".as_ref(),
+            )
+            .title("Chapter 1")
+            .child(Element::new("ch1.xhtml#s1", "Section 1"))
+            .child(Element::new("ch1.xhtml#s2", "Section 2")),
+        )
+        .unwrap()
+        .add_landmark("loi", "Image", "image.png");
+    builder.check_targets().unwrap();
+
+    builder
+        .add_toc_element(Element::new("ch1.xhtml#s3", "Section 3").level(2))
+        .add_toc_element(Element::new("ch2.xhtml", "Chapter 2"))
+        .add_landmark("bodymatter", "Start", "ch1.xhtml#start");
+    let err = builder.check_targets().unwrap_err().to_string();
+    assert!(err.contains("toc target 'ch1.xhtml#s3': no element with id 's3' in 'ch1.xhtml'"));
+    assert!(err.contains("toc target 'ch2.xhtml': 'ch2.xhtml' was not added"));
+    assert!(err.contains("landmark target 'ch1.xhtml#start'"));
+
+    // Page list and navigation lists, in every rendition
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .add_content(EpubContent::new("ch1.xhtml", b"<p id=\"p1\"/>".as_ref()))
+        .unwrap()
+        .add_page_break("1", "ch1.xhtml#p1")
+        .add_page_break("2", "ch1.xhtml#p2")
+        .add_rendition("FXL", Rendition::new())
+        .unwrap()
+        .add_content(EpubContent::new("page1.xhtml", b"".as_ref()))
+        .unwrap()
+        .add_to_nav_list(NavListKind::Illustrations, "Figure 1", "page2.xhtml");
+    let err = builder.check_targets().unwrap_err().to_string();
+    assert!(err.contains(
+        "rendition 'OEBPS': page list target 'ch1.xhtml#p2': no element with id 'p2' in 'ch1.xhtml'"
+    ));
+    assert!(err.contains("rendition 'FXL': loi target 'page2.xhtml': 'page2.xhtml' was not added"));
+    assert!(!err.contains("#p1"));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_epub33() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V33)
+        .metadata(MetadataKind::Lang, "fr")
+        .add_accessibility_metadata("accessibilityFeature", "alternativeText")
+        .add_content(
+            EpubContent::new("ch1.xhtml", b"".as_ref())
+                .title("Chapitre 1")
+                .reftype(ReferenceType::Text),
+        )
+        .unwrap();
+    let mut archive = generate_archive(&mut builder);
+
+    assert!(!archive.file_names().any(|f| f == "OEBPS/toc.ncx"));
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    assert!(opf.contains("<package version=\"3.0\""));
+    assert!(opf.contains("<meta property=\"dcterms:modified\">"));
+    assert!(!opf.contains("toc.ncx"));
+    assert!(!opf.contains("<guide>"));
+    assert!(opf.contains("<spine>"));
+    assert!(!opf.contains("accessMode"));
+    assert!(opf.contains("<meta property=\"schema:accessibilityFeature\">alternativeText</meta>"));
+    assert!(!opf.contains("tableOfContents"));
+    assert!(opf.contains("<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>"));
+    assert!(opf.contains("<itemref idref=\"ch1.xhtml\"/>"));
+    let nav = String::from_utf8(read_archive_file(&mut archive, "OEBPS/nav.xhtml")).unwrap();
+    assert!(nav.contains("xml:lang=\"fr\" lang=\"fr\""));
+    assert!(nav.contains("<nav epub:type = \"toc\" id=\"toc\">"));
+    assert!(nav.contains("<li><a href=\"ch1.xhtml\">Chapitre 1</a></li>"));
+    assert!(nav.contains("<nav epub:type=\"landmarks\" id=\"landmarks\">"));
+    assert!(nav.contains("<li><a epub:type=\"bodymatter\" href=\"ch1.xhtml\">Chapitre 1</a></li>"));
+    // The accessibility metadata that was not set is reported
+    assert_eq!(
+        builder.missing_accessibility_metadata(),
+        ["accessMode", "accessibilityHazard", "accessibilitySummary"]
+    );
+
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder.epub_version(Version::V33).legacy_navigation(true);
+    let mut archive = generate_archive(&mut builder);
+    assert!(archive.file_names().any(|f| f == "OEBPS/toc.ncx"));
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    assert!(opf.contains("<spine toc=\"ncx\">"));
+    assert!(opf.contains("<guide>"));
+    let nav = String::from_utf8(read_archive_file(&mut archive, "OEBPS/nav.xhtml")).unwrap();
+    assert!(!nav.contains("landmarks"));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_legacy_navigation() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .legacy_navigation(false)
+        .add_content(
+            EpubContent::new("ch1.xhtml", b"".as_ref())
+                .title("Chapter 1")
+                .reftype(ReferenceType::Text),
+        )
+        .unwrap();
+    let mut archive = generate_archive(&mut builder);
+    assert!(!archive.file_names().any(|f| f == "OEBPS/toc.ncx"));
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    assert!(!opf.contains("toc.ncx"));
+    assert!(opf.contains("<spine>"));
+    assert!(!opf.contains("<guide>"));
+    let nav = String::from_utf8(read_archive_file(&mut archive, "OEBPS/nav.xhtml")).unwrap();
+    assert!(nav.contains("<nav epub:type=\"landmarks\" id=\"landmarks\">"));
+
+    // Always generated for EPUB 2
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder.epub_version(Version::V20).legacy_navigation(false);
+    let mut archive = generate_archive(&mut builder);
+    assert!(archive.file_names().any(|f| f == "OEBPS/toc.ncx"));
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    assert!(opf.contains("<guide>"));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_profiles() {
+    let mut outputs = vec![Vec::<u8>::new(); 3];
+    let mut slots = outputs.iter_mut();
+    Builder::generate_profiles(
+        &[Profile::Generic, Profile::Kindle, Profile::Kobo],
+        || {
+            let mut builder = Builder::new(crate::ZipLibrary::new()?)?;
+            builder
+                .epub_version(Version::V33)
+                .metadata(MetadataKind::Description, "A book")
+                .add_cover_image("cover.png", b"".as_ref(), "image/png")?
+                .add_content(
+                    EpubContent::new("cover.xhtml", b"".as_ref()).reftype(ReferenceType::Cover),
+                )?
+                .add_content(
+                    EpubContent::new("title.xhtml", b"".as_ref())
+                        .title("Title")
+                        .reftype(ReferenceType::TitlePage),
+                )?
+                .add_content(
+                    EpubContent::new("ch1.xhtml", b"".as_ref())
+                        .title("Chapter 1")
+                        .level(2),
+                )?
+                // Generated, so it is not the start of the book
+                .add_mini_toc("title.xhtml");
+            Ok(builder)
+        },
+        |_| Ok(slots.next().unwrap()),
+    )
+    .unwrap();
+    let mut archives: Vec<_> = outputs
+        .into_iter()
+        .map(|bytes| libzip::ZipArchive::new(io::Cursor::new(bytes)).unwrap())
+        .collect();
+    let ibooks = "META-INF/com.apple.ibooks.display-options.xml";
+
+    let generic = &mut archives[0];
+    assert!(generic.file_names().any(|f| f == ibooks));
+    assert!(!generic.file_names().any(|f| f == "OEBPS/toc.ncx"));
+
+    let kindle = &mut archives[1];
+    assert!(!kindle.file_names().any(|f| f == ibooks));
+    assert!(kindle.file_names().any(|f| f == "OEBPS/toc.ncx"));
+    let opf = String::from_utf8(read_archive_file(kindle, "OEBPS/content.opf")).unwrap();
+    assert!(opf.contains("<meta property=\"dcterms:modified\">"));
+    let cover = opf.find("<meta name=\"cover\"").unwrap();
+    assert!(cover < opf.find("<dc:description>").unwrap());
+    assert!(opf.contains("<reference type=\"cover\" title=\"Cover\" href=\"cover.xhtml\"/>"));
+    assert!(opf.contains("<reference type=\"text\" title=\"Chapter 1\" href=\"ch1.xhtml\"/>"));
+
+    let kobo = &mut archives[2];
+    assert!(!kobo.file_names().any(|f| f == ibooks));
+    let opf = String::from_utf8(read_archive_file(kobo, "OEBPS/content.opf")).unwrap();
+    assert!(opf.contains("<spine toc=\"ncx\">"));
+    assert!(!opf.contains("<reference type=\"text\""));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_kepub() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    assert_eq!(builder.file_extension(), "epub");
+    builder.profile(Profile::Kobo).kepub(true).unwrap();
+    assert_eq!(builder.file_extension(), "kepub.epub");
+    builder
+        .add_content(
+            EpubContent::new(
+                "ch1.xhtml",
+                b"<html><body><p>Hello.</p></body></html>".as_ref(),
+            )
+            .title("Chapter 1"),
+        )
+        .unwrap();
+    // Documents are written as they are added
+    assert!(builder.kepub(false).is_err());
+    let mut archive = generate_archive(&mut builder);
+    let ch1 = String::from_utf8(read_archive_file(&mut archive, "OEBPS/ch1.xhtml")).unwrap();
+    assert_eq!(
+        ch1,
+        "<html><body><div id=\"book-columns\"><div id=\"book-inner\">\
+         <p><span class=\"koboSpan\" id=\"kobo.1.1\">Hello.</span></p>\
+         </div></div></body></html>"
+    );
+    // Generated documents are left as is
+    let nav = String::from_utf8(read_archive_file(&mut archive, "OEBPS/nav.xhtml")).unwrap();
+    assert!(!nav.contains("koboSpan"));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_apple_display_options() {
+    let ibooks = "META-INF/com.apple.ibooks.display-options.xml";
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    let mut archive = generate_archive(&mut builder);
+    let options = String::from_utf8(read_archive_file(&mut archive, ibooks)).unwrap();
+    assert_eq!(
+        options,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<display_options>
+  <platform name="*">
+    <option name="specified-fonts">true</option>
+  </platform>
+</display_options>
+"#
+    );
+
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder.profile(Profile::Kobo).apple_display_options(
+        AppleDisplayOptions::new()
+            .specified_fonts(false)
+            .open_to_spread(true)
+            .orientation_lock(crate::OrientationLock::None),
+    );
+    let mut archive = generate_archive(&mut builder);
+    let options = String::from_utf8(read_archive_file(&mut archive, ibooks)).unwrap();
+    assert!(options.contains(
+        "    <option name=\"specified-fonts\">false</option>
+    <option name=\"open-to-spread\">true</option>
+    <option name=\"orientation-lock\">none</option>"
+    ));
+
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder.omit_apple_display_options();
+    let archive = generate_archive(&mut builder);
+    assert!(!archive.file_names().any(|f| f == ibooks));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_layout() {
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    builder
+        .epub_version(Version::V30)
+        .root_dir("EPUB")
+        .unwrap()
+        .organize_files(true)
+        .unwrap()
+        .add_cover_image("cover.png", b"".as_ref(), "image/png")
+        .unwrap()
+        .add_font("serif.otf", b"".as_ref(), "font/otf", Obfuscation::Idpf)
+        .unwrap()
+        .add_content(
+            EpubContent::new("ch1.xhtml", b"<p id=\"p1\"/>".as_ref())
+                .title("Chapter 1")
+                .reftype(ReferenceType::Text),
+        )
+        .unwrap()
+        .add_page_break("1", "ch1.xhtml#p1")
+        .add_resource("images/a.png", b"".as_ref(), "image/png")
+        .unwrap()
+        .add_toc_element(Element::new("images/a.png", "Map"))
+        .add_to_nav_list(NavListKind::Illustrations, "Map", "images/a.png")
+        .inline_nav_list(NavListKind::Illustrations)
+        .inline_toc();
+    assert!(builder.root_dir("OEBPS").is_err());
+    let mut archive = generate_archive(&mut builder);
+
+    for file in [
+        "EPUB/content.opf",
+        "EPUB/toc.ncx",
+        "EPUB/nav.xhtml",
+        "EPUB/Text/ch1.xhtml",
+        "EPUB/Text/toc.xhtml",
+        "EPUB/Images/cover.png",
+        "EPUB/Styles/stylesheet.css",
+        "EPUB/Fonts/serif.otf",
+    ] {
+        assert!(archive.file_names().any(|f| f == file), "missing {file}");
+    }
+    let container =
+        String::from_utf8(read_archive_file(&mut archive, "META-INF/container.xml")).unwrap();
+    assert!(container.contains("full-path=\"EPUB/content.opf\""));
+    let opf = String::from_utf8(read_archive_file(&mut archive, "EPUB/content.opf")).unwrap();
+    assert!(opf.contains("href=\"Text/ch1.xhtml\""));
+    assert!(opf.contains("href=\"Images/cover.png\""));
+    assert!(opf.contains("<reference type=\"text\" title=\"Chapter 1\" href=\"Text/ch1.xhtml\"/>"));
+    let nav = String::from_utf8(read_archive_file(&mut archive, "EPUB/nav.xhtml")).unwrap();
+    assert!(nav.contains("<a href=\"Text/ch1.xhtml\">Chapter 1</a>"));
+    assert!(nav.contains("<a href=\"Text/ch1.xhtml#p1\">1</a>"));
+    assert!(nav.contains("href=\"Styles/stylesheet.css\""));
+    let ncx = String::from_utf8(read_archive_file(&mut archive, "EPUB/toc.ncx")).unwrap();
+    assert!(ncx.contains("<content src=\"Text/ch1.xhtml\"/>"));
+    let toc = String::from_utf8(read_archive_file(&mut archive, "EPUB/Text/toc.xhtml")).unwrap();
+    assert!(toc.contains("<a href=\"ch1.xhtml\">Chapter 1</a>"));
+    assert!(toc.contains("href=\"../Styles/stylesheet.css\""));
+    // Links of generated documents to files which are not in `Text/`
+    assert!(toc.contains("<a href=\"../Images/images/a.png\">Map</a>"));
+    let loi = String::from_utf8(read_archive_file(&mut archive, "EPUB/Text/loi.xhtml")).unwrap();
+    assert!(loi.contains("<a href=\"../Images/images/a.png\">Map</a>"));
+    let encryption =
+        String::from_utf8(read_archive_file(&mut archive, "META-INF/encryption.xml")).unwrap();
+    assert!(encryption.contains("URI=\"EPUB/Fonts/serif.otf\""));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_renditions() {
+    use crate::{Rendition, RenditionLayout};
+
+    let mut builder = Builder::new(crate::ZipLibrary::new().unwrap()).unwrap();
+    assert!(builder.add_rendition("FXL", Rendition::new()).is_err());
+    builder
+        .epub_version(Version::V30)
+        .rendition(Rendition::new().language("en"))
+        .add_font("serif.otf", b"".as_ref(), "font/otf", Obfuscation::Idpf)
+        .unwrap()
+        .add_content(EpubContent::new("ch1.xhtml", b"".as_ref()).title("Chapter 1"))
+        .unwrap()
+        .add_rendition(
+            "FXL",
+            Rendition::new()
+                .layout(RenditionLayout::PrePaginated)
+                .label("Fixed"),
+        )
+        .unwrap()
+        .add_font("serif.otf", b"".as_ref(), "font/otf", Obfuscation::Idpf)
+        .unwrap()
+        .add_content(EpubContent::new("page1.xhtml", b"".as_ref()).title("Page 1"))
+        .unwrap()
+        .rendition_mapping(true);
+    assert!(builder.add_rendition("OEBPS", Rendition::new()).is_err());
+    let mut archive = generate_archive(&mut builder);
+
+    let container =
+        String::from_utf8(read_archive_file(&mut archive, "META-INF/container.xml")).unwrap();
+    assert!(container.contains("xmlns:rendition=\"http://www.idpf.org/vocab/rendition/#\""));
+    assert!(container.contains(
+        "<rootfile full-path=\"OEBPS/content.opf\" \
+         media-type=\"application/oebps-package+xml\" rendition:language=\"en\" />"
+    ));
+    assert!(container.contains(
+        "<rootfile full-path=\"FXL/content.opf\" media-type=\"application/oebps-package+xml\" \
+         rendition:layout=\"pre-paginated\" rendition:label=\"Fixed\" />"
+    ));
+    assert!(container.contains("<link href=\"mapping.xhtml\" rel=\"mapping\""));
+    let mapping = String::from_utf8(read_archive_file(&mut archive, "mapping.xhtml")).unwrap();
+    assert!(mapping.contains("FXL/content.opf#epubcfi(/6/2)"));
+
+    let opf = String::from_utf8(read_archive_file(&mut archive, "OEBPS/content.opf")).unwrap();
+    assert!(opf.contains("href=\"ch1.xhtml\""));
+    assert!(!opf.contains("page1.xhtml"));
+    assert!(!opf.contains("rendition:layout"));
+    let opf = String::from_utf8(read_archive_file(&mut archive, "FXL/content.opf")).unwrap();
+    assert!(opf.contains("href=\"page1.xhtml\""));
+    assert!(!opf.contains("ch1.xhtml"));
+    assert!(opf.contains("<meta property=\"rendition:layout\">pre-paginated</meta>"));
+    let nav = String::from_utf8(read_archive_file(&mut archive, "FXL/nav.xhtml")).unwrap();
+    assert!(nav.contains("Page 1"));
+    assert!(!nav.contains("Chapter 1"));
+    assert!(archive.file_names().any(|f| f == "FXL/stylesheet.css"));
+
+    let encryption =
+        String::from_utf8(read_archive_file(&mut archive, "META-INF/encryption.xml")).unwrap();
+    assert!(encryption.contains("URI=\"OEBPS/serif.otf\""));
+    assert!(encryption.contains("URI=\"FXL/serif.otf\""));
+}
+
+#[test]
+#[cfg(feature = "zip-library")]
+fn builder_zip_stream() {
+    let mut epub = io::Cursor::new(Vec::<u8>::new());
+    let mut builder = Builder::new(crate::ZipStream::new(&mut epub).unwrap()).unwrap();
+    builder
+        .add_resource("audio.mp3", vec![0_u8; 1 << 16].as_slice(), "audio/mpeg")
+        .unwrap()
+        .add_content(
+            EpubContent::new("ch1.xhtml", b"<p id=\"a\"/>".as_ref())
+                .title("Chapter 1")
+                .child(Element::new("ch1.xhtml#a", "A")),
+        )
+        .unwrap();
+    // The document is copied as it is, and its ids collected along the way
+    builder.check_targets().unwrap();
+    // The EPUB is written into the destination of the ZipStream
+    assert!(builder.generate(&mut vec![]).is_err());
+    builder.finish().unwrap();
+    // The archive is complete, so it can't be finished again
+    let error = builder.finish().unwrap_err();
+    assert_eq!(error.to_string(), "the EPUB was already generated");
+    drop(builder);
+
+    let mut archive = libzip::ZipArchive::new(io::Cursor::new(epub.into_inner())).unwrap();
+    assert_eq!(
+        read_archive_file(&mut archive, "mimetype"),
+        b"application/epub+zip"
+    );
+    assert_eq!(
+        read_archive_file(&mut archive, "OEBPS/audio.mp3").len(),
+        1 << 16
+    );
+    assert_eq!(
+        read_archive_file(&mut archive, "OEBPS/ch1.xhtml"),
+        b"<p id=\"a\"/>"
+    );
+}
+
+// Generate the EPUB and open the result as a zip archive
+#[cfg(all(test, feature = "zip-library"))]
+fn generate_archive<Z: Zip>(builder: &mut Builder<Z>) -> libzip::ZipArchive<io::Cursor<Vec<u8>>> {
+    let mut epub = vec![];
+    builder.generate(&mut epub).unwrap();
+    libzip::ZipArchive::new(io::Cursor::new(epub)).unwrap()
+}
+
+// Read a file from a generated EPUB
+#[cfg(all(test, feature = "zip-library"))]
+fn read_archive_file(archive: &mut libzip::ZipArchive<io::Cursor<Vec<u8>>>, name: &str) -> Vec<u8> {
+    let mut content = vec![];
+    archive
+        .by_name(name)
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap();
+    content
+}
@@ -3,6 +3,7 @@
 // this file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use crate::Element;
+use crate::MediaOverlay;
 
 use std::io::Read;
 
@@ -51,6 +52,77 @@ pub enum ReferenceType {
     Text,
 }
 
+impl ReferenceType {
+    /// The type of this reference in the guide section of EPUB 2.0
+    pub(crate) const fn guide_type(self) -> &'static str {
+        match self {
+            Self::Cover => "cover",
+            Self::TitlePage => "title-page",
+            Self::Toc => "toc",
+            Self::Index => "index",
+            Self::Glossary => "glossary",
+            Self::Acknowledgements => "acknowledgements",
+            Self::Bibliography => "bibliography",
+            Self::Colophon => "colophon",
+            Self::Copyright => "copyright",
+            Self::Dedication => "dedication",
+            Self::Epigraph => "epigraph",
+            Self::Foreword => "foreword",
+            Self::Loi => "loi",
+            Self::Lot => "lot",
+            Self::Notes => "notes",
+            Self::Preface => "preface",
+            Self::Text => "text",
+        }
+    }
+
+    /// The `epub:type` of this reference in the landmarks of EPUB 3.0
+    pub(crate) const fn landmark_type(self) -> &'static str {
+        match self {
+            Self::Cover => "cover",
+            Self::Text => "bodymatter",
+            Self::Toc => "toc",
+            Self::Bibliography => "bibliography",
+            Self::Epigraph => "epigraph",
+            Self::Foreword => "foreword",
+            Self::Preface => "preface",
+            Self::Notes => "endnotes",
+            Self::Loi => "loi",
+            Self::Lot => "lot",
+            Self::Colophon => "colophon",
+            Self::TitlePage => "titlepage",
+            Self::Index => "index",
+            Self::Glossary => "glossary",
+            Self::Copyright => "copyright-page",
+            Self::Acknowledgements => "acknowledgements",
+            Self::Dedication => "dedication",
+        }
+    }
+
+    /// Label used for a landmark when the content has no title
+    pub(crate) const fn default_title(self) -> &'static str {
+        match self {
+            Self::Cover => "Cover",
+            Self::Text => "Start of content",
+            Self::Toc => "Table of contents",
+            Self::Bibliography => "Bibliography",
+            Self::Epigraph => "Epigraph",
+            Self::Foreword => "Foreword",
+            Self::Preface => "Preface",
+            Self::Notes => "Notes",
+            Self::Loi => "List of illustrations",
+            Self::Lot => "List of tables",
+            Self::Colophon => "Colophon",
+            Self::TitlePage => "Title page",
+            Self::Index => "Index",
+            Self::Glossary => "Glossary",
+            Self::Copyright => "Copyright",
+            Self::Acknowledgements => "Acknowledgements",
+            Self::Dedication => "Dedication",
+        }
+    }
+}
+
 /// Represents a XHTML file that can be added to an EPUB document.
 ///
 /// This struct is designed to be used with the `add_content` method
@@ -77,8 +149,10 @@ pub struct EpubContent<R: Read> {
     pub toc: Element,
     /// The content
     pub content: R,
-    /// Properties. See [EpubProperties](enum.EpubProperties.html)
+    /// Properties. See [`ReferenceType`](enum.ReferenceType.html)
     pub reftype: Option<ReferenceType>,
+    /// Synchronized audio narration (EPUB 3 only), set with `media_overlay`
+    pub(crate) media_overlay: Option<MediaOverlay>,
 }
 
 impl<R: Read> EpubContent<R> {
@@ -91,6 +165,7 @@ impl<R: Read> EpubContent<R> {
             content,
             toc: Element::new(href, ""),
             reftype: None,
+            media_overlay: None,
         }
     }
 
@@ -99,6 +174,17 @@ impl<R: Read> EpubContent<R> {
     #[must_use]
     pub fn title<S: Into<String>>(mut self, title: S) -> Self {
         self.toc.title = title.into();
+        self.toc.title_markup = None;
+        self
+    }
+
+    /// Set the title of this content as XHTML markup, for the navigation document and
+    /// the inline toc. A plain-text title, used elsewhere, is derived from it.
+    ///
+    /// See [`Element::title_markup`](struct.Element.html#method.title_markup).
+    #[must_use]
+    pub fn title_markup<S: Into<String>>(mut self, markup: S) -> Self {
+        self.toc = self.toc.title_markup(markup);
         self
     }
 
@@ -110,6 +196,17 @@ impl<R: Read> EpubContent<R> {
         self
     }
 
+    /// Excludes this content from the numbering of the table of contents, e.g. for
+    /// front matter
+    ///
+    /// See [`Numbering`](enum.Numbering.html).
+    #[must_use]
+    #[allow(clippy::missing_const_for_fn)]
+    pub fn unnumbered(mut self) -> Self {
+        self.toc = self.toc.unnumbered();
+        self
+    }
+
     /// Adds a sublevel to the toc
     #[must_use]
     pub fn child(mut self, elem: Element) -> Self {
@@ -139,4 +236,15 @@ impl<R: Read> EpubContent<R> {
         self.reftype = Some(reftype);
         self
     }
+
+    /// Attaches a media overlay (synchronized audio narration) to this content
+    ///
+    /// This is only supported by EPUB 3; it is ignored when generating an EPUB 2 file.
+    ///
+    /// See [`MediaOverlay`](struct.MediaOverlay.html).
+    #[must_use]
+    pub fn media_overlay(mut self, overlay: MediaOverlay) -> Self {
+        self.media_overlay = Some(overlay);
+        self
+    }
 }
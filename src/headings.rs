@@ -0,0 +1,160 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::common;
+use crate::Element;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::ops::RangeInclusive;
+
+/// Matches a `h1`–`h6` element: level, attributes and content
+static HEADING: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?is)<h([1-6])(\s[^>]*)?>(.*?)</h[1-6]\s*>")
+        .expect("error compiling heading regex")
+});
+
+/// A heading found in a XHTML document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    /// Level of the heading, from 1 (`h1`) to 6 (`h6`)
+    pub level: i32,
+    /// The `id` of the heading, either existing or generated
+    pub id: String,
+    /// The text of the heading, without markup
+    pub title: String,
+}
+
+/// Find the headings of a XHTML document whose level is in `levels`
+///
+/// Headings without an `id` are given one (`heading-1`, `heading-2`, ...), so the
+/// returned document should be written instead of the original one. Headings without
+/// any text are ignored.
+pub fn extract(xhtml: &str, levels: &RangeInclusive<i32>) -> (String, Vec<Heading>) {
+    let mut output = String::with_capacity(xhtml.len());
+    let mut headings = vec![];
+    let mut last = 0;
+    let mut generated = 0;
+    let ids = common::ids(xhtml);
+    for caps in HEADING.captures_iter(xhtml) {
+        let level: i32 = caps[1].parse().expect("heading level is a digit");
+        let title = common::plain_text(&caps[3]);
+        if !levels.contains(&level) || title.is_empty() {
+            continue;
+        }
+        let attributes = caps.get(2).map_or("", |m| m.as_str());
+        let existing = common::id_attribute(attributes).map(str::to_owned);
+        let id = existing.unwrap_or_else(|| {
+            let id = loop {
+                generated += 1;
+                let id = format!("heading-{generated}");
+                if !ids.contains_key(&id) {
+                    break id;
+                }
+            };
+            // Right after `<hN`
+            let insert_at = caps.get(0).expect("whole match").start() + 3;
+            output.push_str(&xhtml[last..insert_at]);
+            output.push_str(" id=\"");
+            output.push_str(&id);
+            output.push('"');
+            last = insert_at;
+            id
+        });
+        headings.push(Heading { level, id, title });
+    }
+    output.push_str(&xhtml[last..]);
+    (output, headings)
+}
+
+/// Build the toc entry of a document from its headings
+///
+/// The first heading gives the title of `root`, unless it already has one, and the
+/// following ones are nested under it according to their levels. Existing children
+/// of `root` are kept, in which case the headings are only used for the title.
+pub fn to_element(mut root: Element, headings: &[Heading]) -> Element {
+    let Some((first, rest)) = headings.split_first() else {
+        return root;
+    };
+    if root.title.is_empty() {
+        root.title.clone_from(&first.title);
+    }
+    if !root.children.is_empty() {
+        return root;
+    }
+    for heading in rest {
+        let level = (root.level + heading.level - first.level).max(root.level + 1);
+        root.add(
+            Element::new(
+                format!("{}#{}", root.url, heading.id),
+                heading.title.as_str(),
+            )
+            .level(level),
+        );
+    }
+    root
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+///                                  TESTS                                     //
+/////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn headings_extract() {
+    let xhtml = r#"<body>
+<h1 class="title">Chapter <em>1</em></h1>
+<h2 id="s1">Section &amp; 1</h2>
+<h3>Too deep</h3>
+<h2></h2>
+<h2 id='s2'>Section 2</h2>
+</body>"#;
+    let (output, headings) = extract(xhtml, &(1..=2));
+    assert_eq!(
+        output,
+        r#"<body>
+<h1 id="heading-1" class="title">Chapter <em>1</em></h1>
+<h2 id="s1">Section &amp; 1</h2>
+<h3>Too deep</h3>
+<h2></h2>
+<h2 id='s2'>Section 2</h2>
+</body>"#
+    );
+    let titles: Vec<_> = headings
+        .iter()
+        .map(|h| (h.level, h.id.as_str(), h.title.as_str()))
+        .collect();
+    assert_eq!(
+        titles,
+        [
+            (1, "heading-1", "Chapter 1"),
+            (2, "s1", "Section & 1"),
+            (2, "s2", "Section 2")
+        ]
+    );
+}
+
+#[test]
+fn headings_extract_id_clash() {
+    let (output, headings) = extract("<h1>One</h1><p id='heading-1'></p>", &(1..=1));
+    assert_eq!(
+        output,
+        "<h1 id=\"heading-2\">One</h1><p id='heading-1'></p>"
+    );
+    assert_eq!(headings[0].id, "heading-2");
+}
+
+#[test]
+fn headings_to_element() {
+    let (_, headings) = extract("<h2>Chapter</h2><h3>A</h3><h4>A.1</h4><h3>B</h3>", &(1..=6));
+    let elem = to_element(Element::new("ch1.xhtml", ""), &headings);
+    assert_eq!(elem.title, "Chapter");
+    assert_eq!(elem.children.len(), 2);
+    assert_eq!(elem.children[0].url, "ch1.xhtml#heading-2");
+    assert_eq!(elem.children[0].children[0].title, "A.1");
+    assert_eq!(elem.children[1].level, 2);
+
+    let elem = to_element(Element::new("ch1.xhtml", "Explicit"), &headings);
+    assert_eq!(elem.title, "Explicit");
+    assert_eq!(elem.children.len(), 2);
+}
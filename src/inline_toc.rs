@@ -0,0 +1,90 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/// Options of the inline table of contents, i.e. the `toc.xhtml` page that is part of
+/// the linear document.
+///
+/// This is designed to be used with the
+/// [`inline_toc_with`](struct.Builder.html#method.inline_toc_with) method of the builder.
+///
+/// # Example
+///
+/// ```
+/// use epub_builder::InlineToc;
+///
+/// // Second document of the spine, only showing the chapters of the second part
+/// let options = InlineToc::new()
+///     .position(1)
+///     .title("Contents of Part II")
+///     .root("part_2.xhtml")
+///     .depth(1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InlineToc {
+    /// Position of the page in the spine (0 being the first document). By default, the
+    /// page is inserted after the contents added before the call to `inline_toc_with`.
+    pub position: Option<usize>,
+    /// Title of the page. By default, the `toc_name` metadata, as it is when the
+    /// EPUB is generated.
+    pub title: Option<String>,
+    /// Mustache template of the page, see the `template` method.
+    pub template: Option<String>,
+    /// Maximum number of levels that are displayed
+    pub depth: Option<usize>,
+    /// Url of the toc element whose children are displayed, instead of the whole toc
+    pub root: Option<String>,
+}
+
+impl InlineToc {
+    /// Creates new options, with the default behaviour
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            position: None,
+            title: None,
+            template: None,
+            depth: None,
+            root: None,
+        }
+    }
+
+    /// Sets the position of the page in the spine, 0 being the first document
+    #[must_use]
+    pub const fn position(mut self, position: usize) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    /// Sets the title of the page, instead of the `toc_name` metadata
+    #[must_use]
+    pub fn title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Sets the mustache template used to render the page.
+    ///
+    /// The following variables are available: `title` (escaped), `content` (the
+    /// rendered list), `generator`, `lang`, and `stylesheet` (the href of the
+    /// stylesheet).
+    #[must_use]
+    pub fn template<S: Into<String>>(mut self, template: S) -> Self {
+        self.template = Some(template.into());
+        self
+    }
+
+    /// Sets the maximum number of levels that are displayed
+    #[must_use]
+    pub const fn depth(mut self, depth: usize) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    /// Only displays the children of the toc element whose url is `url`
+    #[must_use]
+    pub fn root<S: Into<String>>(mut self, url: S) -> Self {
+        self.root = Some(url.into());
+        self
+    }
+}
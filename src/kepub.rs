@@ -0,0 +1,211 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Matches a tag, a comment, a CDATA section or a declaration
+static TOKEN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?s)<!--.*?-->|<!\[CDATA\[.*?\]\]>|<[^>]*>").expect("error compiling token regex")
+});
+
+/// Matches the name of a tag
+static TAG_NAME: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^<(/)?(?:[\w.-]+:)?([\w.-]+)").expect("error compiling tag name regex")
+});
+
+/// Matches the start tag of the body
+static BODY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)<body(\s[^>]*)?>").expect("error compiling body regex"));
+
+/// Matches the end of a sentence, including the following spaces
+static SENTENCE_END: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"[.!?…]+["'”’»)]*\s+"#).expect("error compiling sentence regex"));
+
+/// Elements that start a new paragraph, i.e. a new first number of the span ids
+const BLOCKS: [&str; 18] = [
+    "p",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "div",
+    "li",
+    "dt",
+    "dd",
+    "blockquote",
+    "pre",
+    "td",
+    "th",
+    "caption",
+    "figcaption",
+    "aside",
+];
+
+/// Elements whose content is left as is
+const SKIPPED: [&str; 5] = ["script", "style", "svg", "math", "head"];
+
+/// Numbering of the `koboSpan` elements
+#[derive(Default)]
+struct Spans {
+    paragraph: usize,
+    segment: usize,
+}
+
+impl Spans {
+    /// Returns the start tag of the next span
+    fn open(&mut self) -> String {
+        self.paragraph = self.paragraph.max(1);
+        self.segment += 1;
+        format!(
+            "<span class=\"koboSpan\" id=\"kobo.{}.{}\">",
+            self.paragraph, self.segment
+        )
+    }
+
+    /// Starts a new paragraph
+    const fn next_paragraph(&mut self) {
+        self.paragraph += 1;
+        self.segment = 0;
+    }
+}
+
+/// Rewrite a XHTML document to the KEPUB format
+///
+/// Sentences and images are wrapped in `koboSpan` elements, and the content of the
+/// body is wrapped in the `book-columns` and `book-inner` divs. Documents that already
+/// contain `koboSpan` elements are returned unchanged.
+pub fn transform(xhtml: &str) -> String {
+    if xhtml.contains("koboSpan") {
+        return xhtml.to_owned();
+    }
+    let (start, end) = BODY.find(xhtml).map_or((0, xhtml.len()), |body| {
+        let end = xhtml.rfind("</body>").unwrap_or(xhtml.len());
+        (body.end(), end.max(body.end()))
+    });
+    let mut output = String::with_capacity(xhtml.len() * 2);
+    output.push_str(&xhtml[..start]);
+    if start > 0 {
+        output.push_str("<div id=\"book-columns\"><div id=\"book-inner\">");
+    }
+
+    let body = &xhtml[start..end];
+    let mut spans = Spans::default();
+    // Name and nesting of the element being skipped
+    let mut skipped: Option<(&str, usize)> = None;
+    let mut last = 0;
+    for token in TOKEN.find_iter(body) {
+        let text = &body[last..token.start()];
+        if skipped.is_some() {
+            output.push_str(text);
+        } else {
+            wrap_text(text, &mut spans, &mut output);
+        }
+        last = token.end();
+
+        let tag = token.as_str();
+        let Some(caps) = TAG_NAME.captures(tag) else {
+            output.push_str(tag);
+            continue;
+        };
+        let name = caps.get(2).map_or("", |m| m.as_str());
+        let is_end = caps.get(1).is_some();
+        let is_empty = tag.ends_with("/>");
+        if let Some((skipped_name, nesting)) = skipped {
+            if name == skipped_name && !is_empty {
+                skipped = match (is_end, nesting) {
+                    (true, 0) => None,
+                    (true, _) => Some((skipped_name, nesting - 1)),
+                    (false, _) => Some((skipped_name, nesting + 1)),
+                };
+            }
+            output.push_str(tag);
+        } else if is_end {
+            output.push_str(tag);
+        } else if SKIPPED.contains(&name) {
+            if !is_empty {
+                skipped = Some((name, 0));
+            }
+            output.push_str(tag);
+        } else if name == "img" {
+            spans.next_paragraph();
+            output.push_str(&spans.open());
+            output.push_str(tag);
+            output.push_str("</span>");
+        } else {
+            if BLOCKS.contains(&name) {
+                spans.next_paragraph();
+            }
+            output.push_str(tag);
+        }
+    }
+    let text = &body[last..];
+    if skipped.is_some() {
+        output.push_str(text);
+    } else {
+        wrap_text(text, &mut spans, &mut output);
+    }
+
+    if start > 0 {
+        output.push_str("</div></div>");
+    }
+    output.push_str(&xhtml[end..]);
+    output
+}
+
+/// Wrap each sentence of a text node in a span
+fn wrap_text(text: &str, spans: &mut Spans, output: &mut String) {
+    let trimmed = text.trim_start();
+    output.push_str(&text[..text.len() - trimmed.len()]);
+    if trimmed.is_empty() {
+        return;
+    }
+    let mut last = 0;
+    for end in SENTENCE_END.find_iter(trimmed) {
+        output.push_str(&spans.open());
+        output.push_str(&trimmed[last..end.end()]);
+        output.push_str("</span>");
+        last = end.end();
+    }
+    if last < trimmed.len() {
+        output.push_str(&spans.open());
+        output.push_str(&trimmed[last..]);
+        output.push_str("</span>");
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+///                                  TESTS                                     //
+/////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn kepub_transform() {
+    let xhtml = r#"<html>
+<head><title>Title</title><style>p { margin: 0 }</style></head>
+<body class="text">
+<h1>Chapter 1</h1>
+<p>First sentence. Second <em>one</em>!</p>
+<!-- comment -->
+<p><img src="a.png" alt=""/></p>
+<svg><text>Not wrapped</text></svg>
+</body>
+</html>"#;
+    assert_eq!(
+        transform(xhtml),
+        r#"<html>
+<head><title>Title</title><style>p { margin: 0 }</style></head>
+<body class="text"><div id="book-columns"><div id="book-inner">
+<h1><span class="koboSpan" id="kobo.1.1">Chapter 1</span></h1>
+<p><span class="koboSpan" id="kobo.2.1">First sentence. </span><span class="koboSpan" id="kobo.2.2">Second </span><em><span class="koboSpan" id="kobo.2.3">one</span></em><span class="koboSpan" id="kobo.2.4">!</span></p>
+<!-- comment -->
+<p><span class="koboSpan" id="kobo.4.1"><img src="a.png" alt=""/></span></p>
+<svg><text>Not wrapped</text></svg>
+</div></div></body>
+</html>"#
+    );
+    let kepub = transform(xhtml);
+    assert_eq!(transform(&kepub), kepub);
+}
@@ -0,0 +1,104 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::Result;
+
+use color_eyre::eyre::bail;
+use std::path::{Component, Path, PathBuf};
+
+/// Directory layout of the package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    /// Directory containing content.opf and the other files of the package
+    pub root: String,
+    /// Whether files are sorted into `Text/`, `Images/`, `Styles/` and `Fonts/`
+    pub organized: bool,
+}
+
+impl Layout {
+    /// The default layout: everything in a flat `OEBPS` directory
+    pub fn new() -> Self {
+        Self {
+            root: String::from("OEBPS"),
+            organized: false,
+        }
+    }
+
+    /// Sets the root directory, after checking it is a valid one
+    pub fn set_root(&mut self, root: &str) -> Result<()> {
+        let root = root.trim_matches('/');
+        let valid = !root.is_empty()
+            && Path::new(root)
+                .components()
+                .all(|c| matches!(c, Component::Normal(name) if name != "META-INF"));
+        if !valid {
+            bail!("invalid root directory for the package: '{root}'");
+        }
+        root.clone_into(&mut self.root);
+        Ok(())
+    }
+
+    /// Path of a file of the given media type, relative to the root directory
+    pub fn place(&self, file: &str, mime: &str) -> String {
+        // in the zip the path is always with forward slashes, on windows it is with backslashes
+        let file = file.replace('\\', "/");
+        match directory(mime) {
+            Some(dir) if self.organized => format!("{dir}/{file}"),
+            _ => file,
+        }
+    }
+
+    /// Path in the zip of a file, given its path relative to the root directory
+    pub fn path(&self, file: &str) -> PathBuf {
+        Path::new(&self.root).join(file)
+    }
+}
+
+/// The directory of files of a media type, when files are organized
+fn directory(mime: &str) -> Option<&'static str> {
+    match mime {
+        "application/xhtml+xml" => Some("Text"),
+        "text/css" => Some("Styles"),
+        "application/vnd.ms-opentype" | "application/font-woff" => Some("Fonts"),
+        _ if mime.starts_with("image/") => Some("Images"),
+        _ if mime.starts_with("font/") || mime.starts_with("application/x-font") => Some("Fonts"),
+        _ => None,
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+///                                  TESTS                                     //
+/////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn layout_place() {
+    let mut layout = Layout::new();
+    assert_eq!(
+        layout.place("text/ch1.xhtml", "application/xhtml+xml"),
+        "text/ch1.xhtml"
+    );
+    assert_eq!(layout.path("ch1.xhtml"), Path::new("OEBPS/ch1.xhtml"));
+
+    layout.organized = true;
+    layout.set_root("/EPUB/").unwrap();
+    assert_eq!(
+        layout.place("ch1.xhtml", "application/xhtml+xml"),
+        "Text/ch1.xhtml"
+    );
+    assert_eq!(layout.place("a/b.png", "image/png"), "Images/a/b.png");
+    assert_eq!(
+        layout.place("stylesheet.css", "text/css"),
+        "Styles/stylesheet.css"
+    );
+    assert_eq!(layout.place("serif.otf", "font/otf"), "Fonts/serif.otf");
+    assert_eq!(layout.place("ch1.mp3", "audio/mpeg"), "ch1.mp3");
+    assert_eq!(
+        layout.path("Text/ch1.xhtml"),
+        Path::new("EPUB/Text/ch1.xhtml")
+    );
+
+    assert!(layout.set_root("").is_err());
+    assert!(layout.set_root("../out").is_err());
+    assert!(layout.set_root("META-INF").is_err());
+}
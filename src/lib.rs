@@ -94,15 +94,18 @@
 //! * `nav.xhtml`
 //! * `manifest.xml`
 //! * `content.opf`
-//! * `com.apple.ibooks.display-options.xml`.
+//! * `com.apple.ibooks.display-options.xml`, depending on the targeted reading system;
+//! * `encryption.xml`, if some fonts need to be obfuscated.
 //!
-//! It also tries to make it easier to have a correct table of contents, and optionally
-//! generate an inline one in the document.
+//! It also tries to make it easier to have a correct table of contents, either declared
+//! by hand or built from the headings of the documents, and optionally generate an inline
+//! one in the document.
 //!
 //! Supported EPUB versions:
 //!
 //! * 2.0.1 (default)
 //! * 3.0.1
+//! * 3.3
 //!
 //! ## Missing features
 //!
@@ -118,7 +121,10 @@
 //!
 //! EPUB files are Zip files, so we need to zip. By default, this library provides
 //! wrappers around both the [Rust zip library](https://crates.io/crates/zip) and calls
-//! to the `zip` command that may (or may not) be installed on your system.
+//! to the `zip` command that may (or may not) be installed on your system. With the
+//! library, the archive can either be kept in memory until the EPUB is generated
+//! (`ZipLibrary`), or streamed into a file as content is added (`ZipStream`, completed
+//! with `Builder::finish`), which is better suited to books with large resources.
 //!
 //! It is possible to disable the compilation (and the dependencies) of either of these
 //! wrappers, using `no-default-features`. (If you don't enable at least one of them this
@@ -130,13 +136,32 @@
 //! version 2.0](https://www.mozilla.org/en-US/MPL/2.0/).
 #![deny(missing_docs)]
 #![warn(clippy::pedantic, clippy::nursery)]
-#![allow(clippy::too_many_lines, clippy::similar_names)]
+#![allow(
+    clippy::too_many_lines,
+    clippy::similar_names,
+    clippy::non_std_lazy_statics
+)]
 
+mod apple_display_options;
 mod common;
 mod epub;
 mod epub_content;
+mod headings;
+mod inline_toc;
+mod kepub;
+mod layout;
+mod media_overlay;
+mod nav_document;
+mod nav_list;
+mod notes;
+mod numbering;
+mod obfuscation;
+mod page_list;
+mod profile;
+mod rendition;
 mod templates;
 mod toc;
+mod toc_import;
 mod zip;
 #[cfg(feature = "zip-command")]
 mod zip_command;
@@ -145,12 +170,26 @@ mod zip_command;
 mod zip_command_or_library;
 #[cfg(feature = "zip-library")]
 mod zip_library;
+#[cfg(feature = "zip-library")]
+mod zip_stream;
 
+pub use apple_display_options::AppleDisplayOptions;
+pub use apple_display_options::OrientationLock;
 pub use epub::Builder;
 pub use epub::MetadataKind;
 pub use epub::Version;
 pub use epub_content::EpubContent;
 pub use epub_content::ReferenceType;
+pub use inline_toc::InlineToc;
+pub use media_overlay::Clip;
+pub use media_overlay::MediaOverlay;
+pub use nav_list::NavListKind;
+pub use notes::NoteStyle;
+pub use numbering::Numbering;
+pub use obfuscation::Obfuscation;
+pub use profile::Profile;
+pub use rendition::Rendition;
+pub use rendition::RenditionLayout;
 pub use toc::Element;
 pub use toc::Toc;
 #[cfg(feature = "zip-command")]
@@ -160,6 +199,8 @@ pub use zip_command::ZipCommand;
 pub use zip_command_or_library::ZipCommandOrLibrary;
 #[cfg(feature = "zip-library")]
 pub use zip_library::ZipLibrary;
+#[cfg(feature = "zip-library")]
+pub use zip_stream::ZipStream;
 
 /// Re-exports the result type used across the library.
 pub use color_eyre::Result;
@@ -0,0 +1,166 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::common;
+
+use std::time::Duration;
+
+/// A clip of a [`MediaOverlay`](struct.MediaOverlay.html): a fragment of text, and the
+/// part of an audio file that narrates it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clip {
+    /// The id of the text fragment in the XHTML document (without the `#`)
+    pub fragment: String,
+    /// The audio file, relative to the root of the EPUB (like the paths given to `add_resource`)
+    pub audio: String,
+    /// Start of the clip in the audio file
+    pub begin: Duration,
+    /// End of the clip in the audio file
+    pub end: Duration,
+}
+
+/// Synchronized audio narration for a XHTML document (EPUB 3 only).
+///
+/// This is designed to be used with the `media_overlay` method of
+/// [`EpubContent`](struct.EpubContent.html). The builder will then generate the
+/// matching SMIL document, and the `media-overlay` and `media:duration` information
+/// in `content.opf`.
+///
+/// The audio files themselves must be added with the `add_resource` method. A media
+/// overlay must have at least one clip, or generating the EPUB fails.
+///
+/// # Example
+///
+/// ```
+/// use epub_builder::{EpubContent, MediaOverlay};
+/// use std::time::Duration;
+///
+/// let content = EpubContent::new("chapter_1.xhtml", "Some XHTML content".as_bytes())
+///     .media_overlay(MediaOverlay::new()
+///         .clip("para1", "audio/chapter_1.mp3", Duration::from_secs(0), Duration::from_millis(4500))
+///         .clip("para2", "audio/chapter_1.mp3", Duration::from_millis(4500), Duration::from_secs(9)));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MediaOverlay {
+    /// The clips, in reading order
+    pub clips: Vec<Clip>,
+}
+
+impl MediaOverlay {
+    /// Creates a new, empty, media overlay
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { clips: vec![] }
+    }
+
+    /// Adds a clip to this media overlay.
+    ///
+    /// # Arguments
+    ///
+    /// * `fragment`: the id of the narrated element in the XHTML document, e.g. `para1`;
+    /// * `audio`: the path of the audio file, e.g. `audio/chapter_1.mp3`;
+    /// * `begin` and `end`: the part of the audio file that narrates this element.
+    #[must_use]
+    pub fn clip<S1, S2>(mut self, fragment: S1, audio: S2, begin: Duration, end: Duration) -> Self
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        self.clips.push(Clip {
+            fragment: fragment.into(),
+            audio: audio.into(),
+            begin,
+            end,
+        });
+        self
+    }
+
+    /// Total duration of the clips of this media overlay
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        self.clips
+            .iter()
+            .map(|clip| clip.end.saturating_sub(clip.begin))
+            .sum()
+    }
+
+    /// Render the `par` elements of the SMIL document for the XHTML file at `href`
+    ///
+    /// `audio_href` gives the href of an audio file, relative to the SMIL document.
+    pub(crate) fn render_smil(&self, href: &str, audio_href: &dyn Fn(&str) -> String) -> String {
+        let pars: Vec<String> = self
+            .clips
+            .iter()
+            .enumerate()
+            .map(|(i, clip)| {
+                format!(
+                    "\
+<par id=\"par-{n}\">
+  <text src=\"{href}#{fragment}\"/>
+  <audio src=\"{audio}\" clipBegin=\"{begin}\" clipEnd=\"{end}\"/>
+</par>",
+                    n = i + 1,
+                    href = common::escape_quote(href),
+                    fragment = common::escape_quote(clip.fragment.as_str()),
+                    audio = common::escape_quote(audio_href(&clip.audio)),
+                    begin = clock_value(clip.begin),
+                    end = clock_value(clip.end),
+                )
+            })
+            .collect();
+        common::indent(pars.join("\n"), 3)
+    }
+}
+
+/// Format a duration as a SMIL full clock value (`h:mm:ss.fff`)
+pub fn clock_value(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!(
+        "{}:{:02}:{:02}.{:03}",
+        secs / 3600,
+        (secs / 60) % 60,
+        secs % 60,
+        duration.subsec_millis()
+    )
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+///                                  TESTS                                     //
+/////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn media_overlay_clock_value() {
+    assert_eq!(clock_value(Duration::from_millis(4500)), "0:00:04.500");
+    assert_eq!(clock_value(Duration::from_secs(3725)), "1:02:05.000");
+}
+
+#[test]
+fn media_overlay_render() {
+    let overlay = MediaOverlay::new()
+        .clip(
+            "p1",
+            "a.mp3",
+            Duration::from_secs(0),
+            Duration::from_secs(2),
+        )
+        .clip(
+            "p2",
+            "a.mp3",
+            Duration::from_secs(2),
+            Duration::from_millis(3250),
+        );
+    assert_eq!(overlay.duration(), Duration::from_millis(3250));
+    let expected = "      <par id=\"par-1\">
+        <text src=\"ch1.xhtml#p1\"/>
+        <audio src=\"../a.mp3\" clipBegin=\"0:00:00.000\" clipEnd=\"0:00:02.000\"/>
+      </par>
+      <par id=\"par-2\">
+        <text src=\"ch1.xhtml#p2\"/>
+        <audio src=\"../a.mp3\" clipBegin=\"0:00:02.000\" clipEnd=\"0:00:03.250\"/>
+      </par>";
+    assert_eq!(
+        overlay.render_smil("ch1.xhtml", &|audio| format!("../{audio}")),
+        expected
+    );
+}
@@ -0,0 +1,94 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::Result;
+
+use color_eyre::eyre::bail;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Matches the opening tag of `nav` elements
+static NAV_TAG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<nav\b[^>]*>").expect("error compiling nav regex"));
+
+/// Matches the `epub:type` attribute of an element
+static EPUB_TYPE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"\bepub:type\s*=\s*(?:"([^"]*)"|'([^']*)')"#)
+        .expect("error compiling epub:type regex")
+});
+
+/// Returns the `epub:type` values of all the `nav` elements of a document
+fn nav_types(xhtml: &str) -> Vec<Vec<&str>> {
+    NAV_TAG
+        .find_iter(xhtml)
+        .map(|tag| {
+            EPUB_TYPE
+                .captures(tag.as_str())
+                .and_then(|c| c.get(1).or_else(|| c.get(2)))
+                .map_or_else(Vec::new, |m| m.as_str().split_whitespace().collect())
+        })
+        .collect()
+}
+
+/// Check that a user-provided navigation document is usable for EPUB 3
+///
+/// It must be a XHTML document declaring the `epub` namespace, with exactly one `toc`
+/// nav element, and at most one `landmarks` and one `page-list` nav elements.
+pub fn validate(xhtml: &str) -> Result<()> {
+    let mut errors: Vec<String> = Vec::new();
+    if !xhtml.contains("http://www.w3.org/1999/xhtml") {
+        errors.push(String::from("it doesn't declare the XHTML namespace"));
+    }
+    if !xhtml.contains("http://www.idpf.org/2007/ops") {
+        errors.push(String::from(
+            "it doesn't declare the epub namespace (http://www.idpf.org/2007/ops)",
+        ));
+    }
+    let types = nav_types(xhtml);
+    for (epub_type, min) in [("toc", 1), ("landmarks", 0), ("page-list", 0)] {
+        let count = types.iter().filter(|t| t.contains(&epub_type)).count();
+        if count < min {
+            errors.push(format!(
+                "it has no nav element with epub:type=\"{epub_type}\""
+            ));
+        } else if count > 1 {
+            errors.push(format!(
+                "it has {count} nav elements with epub:type=\"{epub_type}\", only one is allowed"
+            ));
+        }
+    }
+    if !errors.is_empty() {
+        bail!("invalid navigation document: {}", errors.join("; "));
+    }
+    Ok(())
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+///                                  TESTS                                     //
+/////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn nav_document_valid() {
+    let nav = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<body>
+  <nav epub:type = "toc" id="toc"><ol><li><a href="a.xhtml">A</a></li></ol></nav>
+  <nav epub:type='landmarks' hidden=""><ol><li><a epub:type="bodymatter" href="a.xhtml">A</a></li></ol></nav>
+</body>
+</html>"#;
+    assert!(validate(nav).is_ok());
+}
+
+#[test]
+fn nav_document_invalid() {
+    let nav = r#"<html xmlns="http://www.w3.org/1999/xhtml">
+<body>
+  <nav epub:type="landmarks"></nav>
+  <nav epub:type="landmarks"></nav>
+</body>
+</html>"#;
+    let err = validate(nav).unwrap_err().to_string();
+    assert!(err.contains("epub namespace"));
+    assert!(err.contains("no nav element with epub:type=\"toc\""));
+    assert!(err.contains("2 nav elements with epub:type=\"landmarks\""));
+}
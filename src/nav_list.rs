@@ -0,0 +1,210 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::common;
+use crate::toc::PlayOrder;
+use crate::ReferenceType;
+
+/// The kind of a navigation list, other than the table of contents.
+///
+/// See the [`add_to_nav_list`](struct.Builder.html#method.add_to_nav_list) method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavListKind {
+    /// List of illustrations (figures, plates, ...)
+    Illustrations,
+    /// List of tables
+    Tables,
+}
+
+impl NavListKind {
+    /// The `epub:type` of this list
+    pub(crate) const fn epub_type(self) -> &'static str {
+        match self {
+            Self::Illustrations => "loi",
+            Self::Tables => "lot",
+        }
+    }
+
+    /// The name of the generated inline page for this list
+    pub(crate) const fn file(self) -> &'static str {
+        match self {
+            Self::Illustrations => "loi.xhtml",
+            Self::Tables => "lot.xhtml",
+        }
+    }
+
+    /// The reference type of the generated inline page for this list
+    pub(crate) const fn reftype(self) -> ReferenceType {
+        match self {
+            Self::Illustrations => ReferenceType::Loi,
+            Self::Tables => ReferenceType::Lot,
+        }
+    }
+
+    /// Default title of this list
+    pub(crate) const fn default_title(self) -> &'static str {
+        match self {
+            Self::Illustrations => "List of Illustrations",
+            Self::Tables => "List of Tables",
+        }
+    }
+}
+
+/// An entry of a navigation list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NavTarget {
+    /// The label of this entry, e.g. `Figure 1: a map`
+    pub label: String,
+    /// The location of the figure or table, e.g. `chapter_1.xhtml#fig1`
+    pub href: String,
+}
+
+/// A navigation list, rendered in nav.xhtml, toc.ncx and optionally as an inline page
+#[derive(Debug, Clone)]
+pub struct NavList {
+    /// The kind of this list
+    pub kind: NavListKind,
+    /// The title of this list
+    pub title: String,
+    /// The entries of this list, in reading order
+    pub targets: Vec<NavTarget>,
+    /// Whether an inline page for this list is generated
+    pub inline: bool,
+}
+
+impl NavList {
+    /// Creates a new, empty, list
+    pub fn new(kind: NavListKind) -> Self {
+        Self {
+            kind,
+            title: String::from(kind.default_title()),
+            targets: vec![],
+            inline: false,
+        }
+    }
+
+    /// Replaces the hrefs of all the entries
+    pub fn map_hrefs(&mut self, f: &dyn Fn(&str) -> String) {
+        for target in &mut self.targets {
+            target.href = f(&target.href);
+        }
+    }
+
+    /// Render the entries as a `<ol>` list, with hrefs relative to the document `from`
+    pub fn render_list(&self, from: &str) -> String {
+        let items: Vec<String> = self
+            .targets
+            .iter()
+            .map(|target| {
+                format!(
+                    "<li><a href=\"{href}\">{label}</a></li>",
+                    href = common::escape_quote(common::relative_href(from, &target.href)),
+                    label = html_escape::encode_text(target.label.trim())
+                )
+            })
+            .collect();
+        format!("<ol>\n{}\n</ol>", common::indent(items.join("\n"), 1))
+    }
+
+    /// Render the list as a `nav` element, for EPUB 3's nav.xhtml
+    pub fn render_nav(&self) -> String {
+        if self.targets.is_empty() {
+            return String::new();
+        }
+        common::indent(
+            format!(
+                "<nav epub:type=\"{epub_type}\" id=\"{epub_type}\">\n  <h2>{title}</h2>\n{list}\n</nav>",
+                epub_type = self.kind.epub_type(),
+                title = html_escape::encode_text(&self.title),
+                list = common::indent(self.render_list("nav.xhtml"), 1)
+            ),
+            1,
+        )
+    }
+
+    /// Render the list as a `navList` element, for toc.ncx
+    pub fn render_ncx(&self, play_order: &mut PlayOrder) -> String {
+        if self.targets.is_empty() {
+            return String::new();
+        }
+        let targets: Vec<String> = self
+            .targets
+            .iter()
+            .enumerate()
+            .map(|(i, target)| {
+                format!(
+                    "\
+<navTarget id=\"navTarget-{epub_type}-{id}\" playOrder=\"{order}\">
+  <navLabel>
+   <text>{label}</text>
+  </navLabel>
+  <content src=\"{href}\"/>
+</navTarget>",
+                    epub_type = self.kind.epub_type(),
+                    id = i + 1,
+                    order = play_order.get(&target.href),
+                    label = html_escape::encode_text(target.label.trim()),
+                    href = common::escape_quote(target.href.as_str())
+                )
+            })
+            .collect();
+        common::indent(
+            format!(
+                "\
+<navList>
+  <navLabel>
+   <text>{title}</text>
+  </navLabel>
+{targets}
+</navList>",
+                title = html_escape::encode_text(&self.title),
+                targets = common::indent(targets.join("\n"), 1)
+            ),
+            1,
+        )
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+///                                  TESTS                                     //
+/////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn nav_list_render() {
+    let mut list = NavList::new(NavListKind::Tables);
+    list.targets.push(NavTarget {
+        label: String::from("Table 1"),
+        href: String::from("text/ch1.xhtml#t1"),
+    });
+    assert_eq!(
+        list.render_nav(),
+        "  <nav epub:type=\"lot\" id=\"lot\">
+    <h2>List of Tables</h2>
+    <ol>
+      <li><a href=\"text/ch1.xhtml#t1\">Table 1</a></li>
+    </ol>
+  </nav>"
+    );
+    assert_eq!(
+        list.render_ncx(&mut PlayOrder::new()),
+        "  <navList>
+    <navLabel>
+     <text>List of Tables</text>
+    </navLabel>
+    <navTarget id=\"navTarget-lot-1\" playOrder=\"1\">
+      <navLabel>
+       <text>Table 1</text>
+      </navLabel>
+      <content src=\"text/ch1.xhtml#t1\"/>
+    </navTarget>
+  </navList>"
+    );
+}
+
+#[test]
+fn nav_list_empty() {
+    let list = NavList::new(NavListKind::Illustrations);
+    assert_eq!(list.render_nav(), "");
+    assert_eq!(list.render_ncx(&mut PlayOrder::new()), "");
+}
@@ -0,0 +1,114 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::common;
+
+/// Name of the generated endnotes document
+pub const ENDNOTES_FILE: &str = "endnotes.xhtml";
+
+/// Where the notes added with the [`add_note`](struct.Builder.html#method.add_note)
+/// method are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteStyle {
+    /// Notes are written as asides at the end of the document they belong to
+    Footnotes,
+    /// Notes are collected in a generated endnotes document, at the end of the book
+    Endnotes,
+}
+
+/// A note, and the document it is referenced from
+#[derive(Debug)]
+pub struct Note {
+    /// Unique number of the note in the book, used for ids
+    pub id: usize,
+    /// Label of the note, numbered from 1 in each document
+    pub label: usize,
+    /// The document referencing this note
+    pub chapter: String,
+    /// XHTML content of the note
+    pub content: String,
+}
+
+impl Note {
+    /// Render the link to this note, to be inserted in the chapter
+    ///
+    /// `href` is the (relative) href of the document containing the note.
+    pub fn render_noteref(&self, href: &str, epub3: bool) -> String {
+        format!(
+            "<sup><a {epub_type}class=\"noteref\" href=\"{href}#note-{id}\" id=\"noteref-{id}\">{label}</a></sup>",
+            epub_type = if epub3 { "epub:type=\"noteref\" " } else { "" },
+            href = common::escape_quote(href),
+            id = self.id,
+            label = self.label
+        )
+    }
+
+    /// Render the note itself, with a link back to where it is referenced
+    ///
+    /// `href` is the (relative) href of the chapter, and `kind` either `footnote` or `endnote`.
+    pub fn render(&self, href: &str, kind: &str, epub3: bool) -> String {
+        let (tag, attribute) = if epub3 {
+            ("aside", format!("epub:type=\"{kind}\""))
+        } else {
+            ("div", format!("class=\"{kind}\""))
+        };
+        format!(
+            "\
+<{tag} {attribute} id=\"note-{id}\">
+  <a class=\"note-backlink\" href=\"{href}#noteref-{id}\">{label}.</a>
+{content}
+</{tag}>",
+            id = self.id,
+            href = common::escape_quote(href),
+            label = self.label,
+            content = common::indent(self.content.trim(), 1)
+        )
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+///                                  TESTS                                     //
+/////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn note_render_epub3() {
+    let note = Note {
+        id: 3,
+        label: 1,
+        chapter: String::from("ch1.xhtml"),
+        content: String::from("<p>A note</p>"),
+    };
+    assert_eq!(
+        note.render_noteref("", true),
+        "<sup><a epub:type=\"noteref\" class=\"noteref\" href=\"#note-3\" id=\"noteref-3\">1</a></sup>"
+    );
+    assert_eq!(
+        note.render("", "footnote", true),
+        "<aside epub:type=\"footnote\" id=\"note-3\">
+  <a class=\"note-backlink\" href=\"#noteref-3\">1.</a>
+  <p>A note</p>
+</aside>"
+    );
+}
+
+#[test]
+fn note_render_epub2() {
+    let note = Note {
+        id: 3,
+        label: 2,
+        chapter: String::from("ch1.xhtml"),
+        content: String::from("A note"),
+    };
+    assert_eq!(
+        note.render_noteref("endnotes.xhtml", false),
+        "<sup><a class=\"noteref\" href=\"endnotes.xhtml#note-3\" id=\"noteref-3\">2</a></sup>"
+    );
+    assert_eq!(
+        note.render("ch1.xhtml", "endnote", false),
+        "<div class=\"endnote\" id=\"note-3\">
+  <a class=\"note-backlink\" href=\"ch1.xhtml#noteref-3\">2.</a>
+  A note
+</div>"
+    );
+}
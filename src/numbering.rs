@@ -0,0 +1,206 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::Element;
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+/// Matches a placeholder of a numbering format, e.g. `{2}` or `{1:I}`
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\{([1-9])(?::([1iIaA]))?\}").expect("error compiling placeholder regex")
+});
+
+/// How the entries of the [`Toc`](struct.Toc.html) are numbered.
+///
+/// Numbers are added at the start of the labels, so they appear in the same way in
+/// `nav.xhtml`, `toc.ncx` and the inline table of contents. Elements can opt out
+/// with [`Element::unnumbered`](struct.Element.html#method.unnumbered).
+///
+/// # Example
+///
+/// ```
+/// use epub_builder::{Element, Numbering, Toc};
+/// let mut toc = Toc::new();
+/// toc.numbering(Numbering::Formats(vec![
+///     String::from("Part {1:I}"),
+///     String::from("{2}."),
+///     String::from("{2}.{3}"),
+/// ]));
+/// toc.add(Element::new("preface.xhtml", "Preface").unnumbered())
+///    .add(Element::new("part_1.xhtml", "Beginnings")
+///        .child(Element::new("chapter_1.xhtml", "Birth")));
+/// let html = toc.render(false);
+/// assert!(html.contains("Part I Beginnings"));
+/// assert!(html.contains("1. Birth"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Numbering {
+    /// Hierarchical decimal numbers: `1`, `1.2`, `1.2.3`
+    Decimal,
+    /// Upper-case roman numbers, for each level: `I`, `II`, `III`
+    Roman,
+    /// Upper-case letters, for each level: `A`, `B`, ..., `Z`, `AA`
+    Alpha,
+    /// A format string for each level, the last one being used for deeper levels.
+    ///
+    /// In these strings, `{n}` is replaced by the number of the ancestor at level `n`
+    /// (or of the element itself), from `1` to `9`. A style can be appended: `{n:1}`
+    /// (decimal, the default), `{n:i}` or `{n:I}` (roman), and `{n:a}` or `{n:A}`
+    /// (letters).
+    Formats(Vec<String>),
+}
+
+impl Numbering {
+    /// Returns the number of an element, given the numbers of its ancestors and
+    /// its own, e.g. `[1, 2]` for the second child of the first element
+    #[must_use]
+    pub fn label(&self, path: &[usize]) -> String {
+        let last = path.last().copied().unwrap_or(0);
+        match self {
+            Self::Decimal => path
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("."),
+            Self::Roman => to_roman(last),
+            Self::Alpha => to_alpha(last),
+            Self::Formats(formats) => {
+                let Some(format) = formats
+                    .get(path.len().saturating_sub(1))
+                    .or_else(|| formats.last())
+                else {
+                    return String::new();
+                };
+                PLACEHOLDER
+                    .replace_all(format, |caps: &Captures| {
+                        let Some(n) = caps[1]
+                            .parse::<usize>()
+                            .ok()
+                            .and_then(|level| path.get(level.checked_sub(1)?).copied())
+                        else {
+                            return String::new();
+                        };
+                        match caps.get(2).map(|m| m.as_str()) {
+                            Some("i") => to_roman(n).to_lowercase(),
+                            Some("I") => to_roman(n),
+                            Some("a") => to_alpha(n).to_lowercase(),
+                            Some("A") => to_alpha(n),
+                            _ => n.to_string(),
+                        }
+                    })
+                    .into_owned()
+            }
+        }
+    }
+
+    /// Returns a copy of `elements` with numbers added to their titles
+    pub(crate) fn apply(&self, elements: &[Element]) -> Vec<Element> {
+        let mut elements = elements.to_vec();
+        self.number(&mut elements, &mut vec![]);
+        elements
+    }
+
+    /// Number `elements`, which are the children of the element at `path`
+    fn number(&self, elements: &mut [Element], path: &mut Vec<usize>) {
+        let mut n = 0;
+        for elem in elements {
+            if !elem.numbered || elem.title.is_empty() {
+                continue;
+            }
+            n += 1;
+            path.push(n);
+            let label = self.label(path);
+            if !label.is_empty() {
+                elem.title = format!("{label} {}", elem.title);
+                if let Some(ref mut markup) = elem.title_markup {
+                    markup.insert_str(0, &format!("{} ", html_escape::encode_text(&label)));
+                }
+            }
+            self.number(&mut elem.children, path);
+            path.pop();
+        }
+    }
+}
+
+/// Upper-case roman representation of a number
+fn to_roman(mut n: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut output = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            output.push_str(numeral);
+            n -= value;
+        }
+    }
+    output
+}
+
+/// Upper-case alphabetic representation of a number: `A` to `Z`, then `AA`, `AB`...
+fn to_alpha(mut n: usize) -> String {
+    let mut output = vec![];
+    while n > 0 {
+        n -= 1;
+        output.push(b'A' + u8::try_from(n % 26).expect("less than 26"));
+        n /= 26;
+    }
+    output.reverse();
+    String::from_utf8(output).expect("ASCII letters")
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+///                                  TESTS                                     //
+/////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+#[allow(clippy::literal_string_with_formatting_args)]
+fn numbering_labels() {
+    assert_eq!(to_roman(1994), "MCMXCIV");
+    assert_eq!(to_alpha(1), "A");
+    assert_eq!(to_alpha(28), "AB");
+    assert_eq!(Numbering::Decimal.label(&[1, 2, 3]), "1.2.3");
+    assert_eq!(Numbering::Roman.label(&[1, 4]), "IV");
+    assert_eq!(Numbering::Alpha.label(&[3]), "C");
+    let formats = Numbering::Formats(vec![
+        String::from("Part {1:I}"),
+        String::from("{1:a}.{2:i}"),
+    ]);
+    assert_eq!(formats.label(&[2]), "Part II");
+    assert_eq!(formats.label(&[2, 3]), "b.iii");
+    assert_eq!(formats.label(&[2, 3, 1]), "b.iii");
+}
+
+#[test]
+fn numbering_apply() {
+    let elements = vec![
+        Element::new("preface.xhtml", "Preface")
+            .unnumbered()
+            .child(Element::new("preface.xhtml#1", "Thanks")),
+        Element::new("ch1.xhtml", "One")
+            .child(Element::new("ch1.xhtml#1", "A"))
+            .child(Element::new("ch1.xhtml#2", "B")),
+        Element::new("ch2.xhtml", "").title_markup("<em>Two</em>"),
+    ];
+    let numbered = Numbering::Decimal.apply(&elements);
+    assert_eq!(numbered[0].title, "Preface");
+    assert_eq!(numbered[0].children[0].title, "Thanks");
+    assert_eq!(numbered[1].title, "1 One");
+    assert_eq!(numbered[1].children[1].title, "1.2 B");
+    assert_eq!(numbered[2].title, "2 Two");
+    assert_eq!(numbered[2].title_markup.as_deref(), Some("2 <em>Two</em>"));
+}
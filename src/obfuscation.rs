@@ -0,0 +1,117 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::Result;
+
+use color_eyre::eyre::Context;
+
+/// Number of bytes obfuscated by the IDPF algorithm
+const IDPF_LENGTH: usize = 1040;
+/// Number of bytes obfuscated by the Adobe algorithm
+const ADOBE_LENGTH: usize = 1024;
+
+/// Font obfuscation algorithm used by the [`add_font`](struct.Builder.html#method.add_font)
+/// method.
+///
+/// Obfuscation is not encryption: it only makes it harder to extract a font from an
+/// EPUB file and reuse it elsewhere, which is what most font licences require.
+///
+/// For more information, see <https://www.w3.org/publishing/epub3/epub-ocf.html#sec-font-obfuscation>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Obfuscation {
+    /// The IDPF algorithm, keyed on the package unique identifier. This is the
+    /// algorithm defined by the EPUB specification and the one you should use.
+    Idpf,
+    /// The (older) Adobe algorithm, keyed on the UUID of the book. Some legacy
+    /// reading systems only support this one.
+    Adobe,
+}
+
+impl Obfuscation {
+    /// The URI identifying this algorithm in `encryption.xml`
+    #[must_use]
+    pub const fn algorithm(self) -> &'static str {
+        match self {
+            Self::Idpf => "http://www.idpf.org/2008/embedding",
+            Self::Adobe => "http://ns.adobe.com/pdf/enc#RC",
+        }
+    }
+
+    /// Obfuscate (or de-obfuscate, since the operation is symmetric) font data in place
+    ///
+    /// `identifier` is the unique identifier of the package, as it appears in `content.opf`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the Adobe algorithm is used and `identifier` is not an UUID.
+    pub fn obfuscate(self, identifier: &str, data: &mut [u8]) -> Result<()> {
+        let (key, length) = match self {
+            Self::Idpf => {
+                // Whitespace must be stripped from the identifier before hashing
+                let identifier: String = identifier
+                    .chars()
+                    .filter(|c| !matches!(c, ' ' | '\t' | '\r' | '\n'))
+                    .collect();
+                let key = sha1_smol::Sha1::from(identifier).digest().bytes().to_vec();
+                (key, IDPF_LENGTH)
+            }
+            Self::Adobe => {
+                let uuid = uuid::Uuid::parse_str(identifier.trim()).wrap_err_with(|| {
+                    format!(
+                        "Adobe font obfuscation requires an UUID identifier, got '{identifier}'"
+                    )
+                })?;
+                (uuid.as_bytes().to_vec(), ADOBE_LENGTH)
+            }
+        };
+        for (i, byte) in data.iter_mut().take(length).enumerate() {
+            *byte ^= key[i % key.len()];
+        }
+        Ok(())
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+///                                  TESTS                                     //
+/////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn obfuscation_idpf_round_trip() {
+    let identifier = "urn:uuid:0f4d3b1a-5c2e-4b8f-9a6d-7e1c2b3a4d5e";
+    let original: Vec<u8> = (0..=250).cycle().take(2000).collect();
+    let mut data = original.clone();
+    Obfuscation::Idpf.obfuscate(identifier, &mut data).unwrap();
+    assert_ne!(&data[..IDPF_LENGTH], &original[..IDPF_LENGTH]);
+    assert_eq!(&data[IDPF_LENGTH..], &original[IDPF_LENGTH..]);
+    Obfuscation::Idpf.obfuscate(identifier, &mut data).unwrap();
+    assert_eq!(data, original);
+}
+
+#[test]
+fn obfuscation_idpf_ignores_whitespace() {
+    let mut a = vec![0u8; 64];
+    let mut b = vec![0u8; 64];
+    Obfuscation::Idpf.obfuscate("urn:isbn:123", &mut a).unwrap();
+    Obfuscation::Idpf
+        .obfuscate(" urn:isbn:\t123\n", &mut b)
+        .unwrap();
+    assert_eq!(a, b);
+    // XORing zeroes gives the key itself, which is the SHA-1 of the identifier
+    assert_eq!(
+        &a[..20],
+        &sha1_smol::Sha1::from("urn:isbn:123").digest().bytes()[..]
+    );
+}
+
+#[test]
+fn obfuscation_adobe() {
+    let identifier = "urn:uuid:0f4d3b1a-5c2e-4b8f-9a6d-7e1c2b3a4d5e";
+    let mut data = vec![0u8; 1100];
+    Obfuscation::Adobe.obfuscate(identifier, &mut data).unwrap();
+    assert_eq!(&data[..4], &[0x0f, 0x4d, 0x3b, 0x1a]);
+    assert!(data[ADOBE_LENGTH..].iter().all(|b| *b == 0));
+    assert!(Obfuscation::Adobe
+        .obfuscate("urn:isbn:123", &mut data)
+        .is_err());
+}
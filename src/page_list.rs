@@ -0,0 +1,209 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::common;
+use crate::toc::PlayOrder;
+
+/// A page break of the print edition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageBreak {
+    /// The page label, as printed (e.g. `12` or `xiv`)
+    pub label: String,
+    /// The location of the page break, e.g. `chapter_1.xhtml#page12`
+    pub href: String,
+}
+
+impl PageBreak {
+    /// The NCX page type and value of this page: arabic numbers are `normal` pages,
+    /// roman numbers `front` pages and anything else `special` pages without a value.
+    fn ncx_type(&self) -> (&'static str, Option<u32>) {
+        let label = self.label.trim();
+        match (label.parse::<u32>(), parse_roman(label)) {
+            (Ok(n), _) => ("normal", Some(n)),
+            (_, Some(n)) => ("front", Some(n)),
+            _ => ("special", None),
+        }
+    }
+}
+
+/// The list of page breaks of the print edition, used for the `page-list` navigation
+#[derive(Debug, Clone, Default)]
+pub struct PageList {
+    /// The page breaks, in reading order
+    pub pages: Vec<PageBreak>,
+}
+
+impl PageList {
+    /// Creates a new, empty, page list
+    pub const fn new() -> Self {
+        Self { pages: vec![] }
+    }
+
+    /// Returns `true` if there is no page break
+    pub const fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Total number of pages, for `dtb:totalPageCount`
+    pub const fn total_page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Largest `normal` page number, for `dtb:maxPageNumber`
+    pub fn max_page_number(&self) -> u32 {
+        self.pages
+            .iter()
+            .filter_map(|page| match page.ncx_type() {
+                ("normal", value) => value,
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Replaces the hrefs of all the page breaks
+    pub fn map_hrefs(&mut self, f: &dyn Fn(&str) -> String) {
+        for page in &mut self.pages {
+            page.href = f(&page.href);
+        }
+    }
+
+    /// Render the page list as a `nav` element, for EPUB 3's nav.xhtml
+    pub fn render_nav(&self) -> String {
+        if self.is_empty() {
+            return String::new();
+        }
+        let items: Vec<String> = self
+            .pages
+            .iter()
+            .map(|page| {
+                format!(
+                    "<li><a href=\"{href}\">{label}</a></li>",
+                    href = common::escape_quote(page.href.as_str()),
+                    label = html_escape::encode_text(page.label.trim())
+                )
+            })
+            .collect();
+        common::indent(
+            format!(
+                "<nav epub:type=\"page-list\" hidden=\"hidden\">\n  <ol>\n{}\n  </ol>\n</nav>",
+                common::indent(items.join("\n"), 2)
+            ),
+            1,
+        )
+    }
+
+    /// Render the page list as a `pageList` element, for toc.ncx
+    pub fn render_ncx(&self, play_order: &mut PlayOrder) -> String {
+        if self.is_empty() {
+            return String::new();
+        }
+        let targets: Vec<String> = self
+            .pages
+            .iter()
+            .enumerate()
+            .map(|(i, page)| {
+                let (page_type, value) = page.ncx_type();
+                format!(
+                    "\
+<pageTarget id=\"pageTarget-{id}\" type=\"{page_type}\"{value} playOrder=\"{order}\">
+  <navLabel>
+   <text>{label}</text>
+  </navLabel>
+  <content src=\"{href}\"/>
+</pageTarget>",
+                    id = i + 1,
+                    order = play_order.get(&page.href),
+                    value = value.map_or_else(String::new, |v| format!(" value=\"{v}\"")),
+                    label = html_escape::encode_text(page.label.trim()),
+                    href = common::escape_quote(page.href.as_str())
+                )
+            })
+            .collect();
+        common::indent(
+            format!(
+                "<pageList>\n{}\n</pageList>",
+                common::indent(targets.join("\n"), 1)
+            ),
+            1,
+        )
+    }
+}
+
+/// Parse a (lowercase or uppercase) roman number
+fn parse_roman(s: &str) -> Option<u32> {
+    if s.is_empty() {
+        return None;
+    }
+    let mut total = 0;
+    let mut previous = 0;
+    for c in s.chars().rev() {
+        let value = match c.to_ascii_lowercase() {
+            'i' => 1,
+            'v' => 5,
+            'x' => 10,
+            'l' => 50,
+            'c' => 100,
+            'd' => 500,
+            'm' => 1000,
+            _ => return None,
+        };
+        if value < previous {
+            total -= value;
+        } else {
+            total += value;
+            previous = value;
+        }
+    }
+    Some(total)
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+///                                  TESTS                                     //
+/////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn page_list_roman() {
+    assert_eq!(parse_roman("xiv"), Some(14));
+    assert_eq!(parse_roman("MCMXCIV"), Some(1994));
+    assert_eq!(parse_roman("12"), None);
+    assert_eq!(parse_roman(""), None);
+}
+
+#[test]
+fn page_list_render() {
+    let mut list = PageList::new();
+    for (label, href) in [
+        ("ii", "a.xhtml#ii"),
+        ("1", "b.xhtml#p1"),
+        ("12", "b.xhtml#p12"),
+    ] {
+        list.pages.push(PageBreak {
+            label: label.to_owned(),
+            href: href.to_owned(),
+        });
+    }
+    assert_eq!(list.total_page_count(), 3);
+    assert_eq!(list.max_page_number(), 12);
+    assert_eq!(
+        list.render_nav(),
+        "  <nav epub:type=\"page-list\" hidden=\"hidden\">
+    <ol>
+      <li><a href=\"a.xhtml#ii\">ii</a></li>
+      <li><a href=\"b.xhtml#p1\">1</a></li>
+      <li><a href=\"b.xhtml#p12\">12</a></li>
+    </ol>
+  </nav>"
+    );
+    assert!(list.render_ncx(&mut PlayOrder::new()).starts_with(
+        "  <pageList>
+    <pageTarget id=\"pageTarget-1\" type=\"front\" value=\"2\" playOrder=\"1\">
+      <navLabel>
+       <text>ii</text>
+      </navLabel>
+      <content src=\"a.xhtml#ii\"/>
+    </pageTarget>
+    <pageTarget id=\"pageTarget-2\" type=\"normal\" value=\"1\" playOrder=\"2\">"
+    ));
+}
@@ -0,0 +1,59 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+/// Reading system targeted by the generated EPUB.
+///
+/// Each profile adjusts the generated files and metadata to the quirks of a retail
+/// channel. The content itself is not modified. This is designed to be used with the
+/// [`profile`](struct.Builder.html#method.profile) method of the builder, or with
+/// [`generate_profiles`](struct.Builder.html#method.generate_profiles) to produce
+/// several files from the same book.
+///
+/// Explicit settings of the builder, e.g. `legacy_navigation`, take precedence over
+/// the ones of the profile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Profile {
+    /// No specific reading system (default): the Apple display options are written,
+    /// and the legacy navigation files depend on the EPUB version.
+    #[default]
+    Generic,
+    /// Apple Books: the Apple display options are written, and the `cover` meta is
+    /// placed first in the metadata.
+    AppleBooks,
+    /// Kobo: no Apple display options, and the NCX is always generated since older
+    /// firmwares use it for the table of contents. The `koboSpan` elements of the KEPUB
+    /// format are not added by this profile: they are enabled with the
+    /// [`kepub`](struct.Builder.html#method.kepub) method of the builder.
+    Kobo,
+    /// Kindle (through Send to Kindle or Kindle Previewer): no Apple display options,
+    /// the `cover` meta is placed first, and the guide is always generated, with a
+    /// `text` reference to the start of the book if none was set.
+    Kindle,
+}
+
+impl Profile {
+    /// Whether `com.apple.ibooks.display-options.xml` is written
+    pub(crate) const fn apple_display_options(self) -> bool {
+        matches!(self, Self::Generic | Self::AppleBooks)
+    }
+
+    /// Whether toc.ncx and the guide are generated, if not set on the builder
+    pub(crate) const fn legacy_navigation(self) -> Option<bool> {
+        match self {
+            Self::Kobo | Self::Kindle => Some(true),
+            Self::Generic | Self::AppleBooks => None,
+        }
+    }
+
+    /// Whether the `<meta name="cover">` element comes before the other metadata
+    pub(crate) const fn cover_meta_first(self) -> bool {
+        matches!(self, Self::AppleBooks | Self::Kindle)
+    }
+
+    /// Whether the guide needs a `text` reference
+    pub(crate) const fn guide_start(self) -> bool {
+        matches!(self, Self::Kindle)
+    }
+}
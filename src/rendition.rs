@@ -0,0 +1,228 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::common;
+
+/// Layout of a rendition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenditionLayout {
+    /// The content is reflowed by the reading system
+    Reflowable,
+    /// Fixed layout: each document is a page
+    PrePaginated,
+}
+
+impl RenditionLayout {
+    /// The value of the `rendition:layout` property
+    pub(crate) const fn value(self) -> &'static str {
+        match self {
+            Self::Reflowable => "reflowable",
+            Self::PrePaginated => "pre-paginated",
+        }
+    }
+}
+
+/// Selection attributes of a rendition, in a publication with multiple renditions.
+///
+/// Reading systems use them to choose the rendition to display, e.g. depending on the
+/// size of the screen or on the language of the user. Attributes set to `None` are not
+/// written. This is designed to be used with the
+/// [`rendition`](struct.Builder.html#method.rendition) and
+/// [`add_rendition`](struct.Builder.html#method.add_rendition) methods of the builder.
+///
+/// See <https://www.w3.org/TR/epub-multi-rend-11/> for the expected values.
+///
+/// # Example
+///
+/// ```
+/// use epub_builder::{Rendition, RenditionLayout};
+///
+/// let fixed = Rendition::new()
+///     .layout(RenditionLayout::PrePaginated)
+///     .media("(min-width: 1024px)")
+///     .label("Fixed layout");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Rendition {
+    /// A media query, e.g. `(orientation: landscape)`
+    pub media: Option<String>,
+    /// The layout of the rendition. It is also declared in its content.opf.
+    pub layout: Option<RenditionLayout>,
+    /// The language of the rendition, e.g. `fr`
+    pub language: Option<String>,
+    /// The access mode of the rendition: `auditory`, `tactile`, `textual` or `visual`
+    pub access_mode: Option<String>,
+    /// A human-readable label, e.g. to let the user choose the rendition
+    pub label: Option<String>,
+}
+
+impl Rendition {
+    /// Creates new, empty, selection attributes
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            media: None,
+            layout: None,
+            language: None,
+            access_mode: None,
+            label: None,
+        }
+    }
+
+    /// Sets the media query
+    #[must_use]
+    pub fn media<S: Into<String>>(mut self, media: S) -> Self {
+        self.media = Some(media.into());
+        self
+    }
+
+    /// Sets the layout
+    #[must_use]
+    pub const fn layout(mut self, layout: RenditionLayout) -> Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    /// Sets the language
+    #[must_use]
+    pub fn language<S: Into<String>>(mut self, language: S) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Sets the access mode
+    #[must_use]
+    pub fn access_mode<S: Into<String>>(mut self, access_mode: S) -> Self {
+        self.access_mode = Some(access_mode.into());
+        self
+    }
+
+    /// Sets the label
+    #[must_use]
+    pub fn label<S: Into<String>>(mut self, label: S) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Render the selection attributes of the `rootfile` element, each one preceded by
+    /// a space
+    pub(crate) fn render_attributes(&self) -> String {
+        let attributes = [
+            ("media", self.media.as_deref()),
+            ("layout", self.layout.map(RenditionLayout::value)),
+            ("language", self.language.as_deref()),
+            ("accessMode", self.access_mode.as_deref()),
+            ("label", self.label.as_deref()),
+        ];
+        attributes
+            .into_iter()
+            .filter_map(|(name, value)| {
+                value.map(|value| format!(" rendition:{name}=\"{}\"", common::escape_quote(value)))
+            })
+            .collect()
+    }
+}
+
+/// A package written in the container
+#[derive(Debug, Clone)]
+pub struct RootFile {
+    /// Path of content.opf in the container
+    pub path: String,
+    /// Selection attributes
+    pub rendition: Rendition,
+    /// Number of documents in the spine
+    pub spine_len: usize,
+    /// Broken targets of its navigation, found when it was written
+    pub broken_targets: Vec<String>,
+}
+
+/// Render the rendition mapping document, which links the corresponding documents of
+/// the renditions, matched by their position in the spine
+pub fn render_mapping(root_files: &[RootFile]) -> String {
+    let units = root_files.iter().map(|r| r.spine_len).max().unwrap_or(0);
+    let mut lists: Vec<String> = Vec::new();
+    for unit in 0..units {
+        let items: Vec<String> = root_files
+            .iter()
+            .filter(|r| unit < r.spine_len)
+            .map(|r| {
+                format!(
+                    "<li><a href=\"{path}#epubcfi(/6/{step})\"{attributes}>{label}</a></li>",
+                    path = common::escape_quote(r.path.as_str()),
+                    step = 2 * (unit + 1),
+                    attributes = r.rendition.render_attributes(),
+                    label =
+                        html_escape::encode_text(r.rendition.label.as_deref().unwrap_or(&r.path))
+                )
+            })
+            .collect();
+        lists.push(format!(
+            "<ul>\n{}\n</ul>",
+            common::indent(items.join("\n"), 1)
+        ));
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\"
+      xmlns:rendition=\"http://www.idpf.org/vocab/rendition/#\">
+<head>
+  <meta charset=\"utf-8\" />
+  <title>Rendition mapping</title>
+</head>
+<body>
+  <nav epub:type=\"resource-map\">
+{}
+  </nav>
+</body>
+</html>
+",
+        common::indent(lists.join("\n"), 2)
+    )
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+///                                  TESTS                                     //
+/////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn rendition_attributes() {
+    assert_eq!(Rendition::new().render_attributes(), "");
+    let rendition = Rendition::new()
+        .layout(RenditionLayout::PrePaginated)
+        .language("fr")
+        .label("\"Fixed\"");
+    assert_eq!(
+        rendition.render_attributes(),
+        " rendition:layout=\"pre-paginated\" rendition:language=\"fr\" \
+         rendition:label=\"&quot;Fixed&quot;\""
+    );
+}
+
+#[test]
+fn rendition_mapping() {
+    let root_files = [
+        RootFile {
+            path: String::from("OEBPS/content.opf"),
+            rendition: Rendition::new().label("Reflowable"),
+            spine_len: 1,
+            broken_targets: vec![],
+        },
+        RootFile {
+            path: String::from("FXL/content.opf"),
+            rendition: Rendition::new(),
+            spine_len: 2,
+            broken_targets: vec![],
+        },
+    ];
+    let mapping = render_mapping(&root_files);
+    assert!(mapping.contains(
+        "    <ul>
+      <li><a href=\"OEBPS/content.opf#epubcfi(/6/2)\" rendition:label=\"Reflowable\">Reflowable</a></li>
+      <li><a href=\"FXL/content.opf#epubcfi(/6/2)\">FXL/content.opf</a></li>
+    </ul>
+    <ul>
+      <li><a href=\"FXL/content.opf#epubcfi(/6/4)\">FXL/content.opf</a></li>
+    </ul>"
+    ));
+}
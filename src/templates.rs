@@ -4,14 +4,26 @@
 
 use once_cell::sync::Lazy;
 
-pub static IBOOKS: &[u8] = include_bytes!("../templates/ibooks.xml");
-pub static CONTAINER: &[u8] = include_bytes!("../templates/container.xml");
+pub static CONTAINER: Lazy<::mustache::Template> = Lazy::new(|| {
+    ::mustache::compile_str(include_str!("../templates/container.xml"))
+        .expect("error compiling 'container.xml' template")
+});
 
 pub static TOC_NCX: Lazy<::mustache::Template> = Lazy::new(|| {
     ::mustache::compile_str(include_str!("../templates/toc.ncx"))
         .expect("error compiling 'toc.ncx' template'")
 });
 
+pub static IBOOKS: Lazy<::mustache::Template> = Lazy::new(|| {
+    ::mustache::compile_str(include_str!("../templates/ibooks.xml"))
+        .expect("error compiling 'ibooks.xml' template")
+});
+
+pub static ENCRYPTION_XML: Lazy<::mustache::Template> = Lazy::new(|| {
+    ::mustache::compile_str(include_str!("../templates/encryption.xml"))
+        .expect("error compiling 'encryption.xml' template")
+});
+
 pub mod v2 {
     use once_cell::sync::Lazy;
 
@@ -23,6 +35,10 @@ pub mod v2 {
         ::mustache::compile_str(include_str!("../templates/v2/nav.xhtml"))
             .expect("error compiling 'nav.xhtml' (for EPUB 2.0) template")
     });
+    pub static PAGE_XHTML: Lazy<::mustache::Template> = Lazy::new(|| {
+        ::mustache::compile_str(include_str!("../templates/v2/page.xhtml"))
+            .expect("error compiling 'page.xhtml' (for EPUB 2.0) template")
+    });
 }
 pub mod v3 {
     use once_cell::sync::Lazy;
@@ -35,4 +51,12 @@ pub mod v3 {
         ::mustache::compile_str(include_str!("../templates/v3/nav.xhtml"))
             .expect("error compiling 'nav.xhtml' (for EPUB 3.0) template")
     });
+    pub static PAGE_XHTML: Lazy<::mustache::Template> = Lazy::new(|| {
+        ::mustache::compile_str(include_str!("../templates/v3/page.xhtml"))
+            .expect("error compiling 'page.xhtml' (for EPUB 3.0) template")
+    });
+    pub static OVERLAY_SMIL: Lazy<::mustache::Template> = Lazy::new(|| {
+        ::mustache::compile_str(include_str!("../templates/v3/overlay.smil"))
+            .expect("error compiling 'overlay.smil' (for EPUB 3.0) template")
+    });
 }
@@ -2,6 +2,57 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with
 // this file, You can obtain one at https://mozilla.org/MPL/2.0/.
 use crate::common;
+use crate::toc_import;
+use crate::Numbering;
+use crate::Result;
+
+use color_eyre::eyre::bail;
+use std::borrow::Cow;
+
+use std::collections::HashMap;
+
+/// Assigns the `playOrder` of toc.ncx entries, in reading order.
+///
+/// Entries pointing to the same target share the same value, as required by the NCX
+/// specification.
+#[derive(Debug, Default)]
+pub struct PlayOrder {
+    orders: HashMap<String, u32>,
+    next: u32,
+}
+
+impl PlayOrder {
+    /// Creates a new `PlayOrder`, starting at 1
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the `playOrder` of the given target, allocating a new one if needed
+    pub fn get(&mut self, url: &str) -> u32 {
+        if let Some(order) = self.orders.get(url) {
+            return *order;
+        }
+        self.next += 1;
+        self.orders.insert(url.to_string(), self.next);
+        self.next
+    }
+
+    /// Creates a `PlayOrder` numbering `urls` in reading order, as given by `position`
+    ///
+    /// Other targets are numbered after them, in the order they are requested.
+    pub(crate) fn in_reading_order<'a>(
+        urls: impl Iterator<Item = &'a str>,
+        position: &dyn Fn(&str) -> (usize, usize),
+    ) -> Self {
+        let mut urls: Vec<&str> = urls.collect();
+        urls.sort_by_key(|url| position(url));
+        let mut play_order = Self::new();
+        for url in urls {
+            play_order.get(url);
+        }
+        play_order
+    }
+}
 
 /// An element of the [Table of contents](struct.Toc.html)
 ///
@@ -13,7 +64,11 @@ use crate::common;
 ///     .child(Element::new("chapter_1.xhtml#1", "Chapter 1, section 1")
 ///               .child(Element::new("chapter_1.xhtml#1-1", "Chapter 1, section 1, subsection 1")));
 /// ```
+///
+/// Elements can't be built with a struct literal, since they have private fields: use
+/// `new` or `unlinked`.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct Element {
     /// The level. 0: part, 1: chapter, 2: section, ...
     pub level: i32,
@@ -21,8 +76,14 @@ pub struct Element {
     pub url: String,
     /// Title of this entry
     pub title: String,
+    /// XHTML markup of the title, used instead of `title` in HTML (nav.xhtml and the
+    /// inline toc) if it is set with `title_markup`
+    pub(crate) title_markup: Option<String>,
     /// Inner elements
-    pub children: Vec<Element>,
+    pub children: Vec<Self>,
+    /// Whether this element is numbered, if the Toc uses a [`Numbering`](enum.Numbering.html);
+    /// set with `unnumbered`
+    pub(crate) numbered: bool,
 }
 
 impl Element {
@@ -34,7 +95,85 @@ impl Element {
             level: 1,
             url: url.into(),
             title: title.into(),
+            title_markup: None,
             children: vec![],
+            numbered: true,
+        }
+    }
+
+    /// Creates a new element of the toc that doesn't link anywhere, e.g. to group
+    /// chapters under a part that has no page of its own.
+    ///
+    /// It is rendered as a `<span>` heading in HTML; in toc.ncx, which requires a
+    /// target, it points to the target of its first linked descendant. It must thus
+    /// have linked descendants: otherwise it is skipped, both in HTML (where a `<span>`
+    /// heading must be followed by a list) and in toc.ncx. In HTML, it is also skipped
+    /// if its children are not rendered because of the maximum depth.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use epub_builder::Element;
+    /// Element::unlinked("Part One")
+    ///     .child(Element::new("chapter_1.xhtml", "Chapter 1"))
+    ///     .child(Element::new("chapter_2.xhtml", "Chapter 2"));
+    /// ```
+    pub fn unlinked<S: Into<String>>(title: S) -> Self {
+        Self::new("", title)
+    }
+
+    /// Sets the title of this element as XHTML markup, e.g. to use `<em>`, `<sup>` or
+    /// ruby annotations, which EPUB 3 allows in the navigation document.
+    ///
+    /// The markup is used as is in HTML, so it must be valid XHTML phrasing content;
+    /// the plain-text `title`, used in toc.ncx, the guide and the landmarks, is derived
+    /// from it by stripping the tags.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use epub_builder::Element;
+    /// let elem = Element::new("chapter_1.xhtml", "")
+    ///     .title_markup("On <em>The Origin of Species</em>");
+    /// assert_eq!(elem.title, "On The Origin of Species");
+    /// ```
+    #[must_use]
+    pub fn title_markup<S: Into<String>>(mut self, markup: S) -> Self {
+        let markup = markup.into();
+        self.title = common::plain_text(&markup);
+        self.title_markup = Some(markup);
+        self
+    }
+
+    /// Returns `true` if this element has a target
+    #[must_use]
+    pub const fn is_linked(&self) -> bool {
+        !self.url.is_empty()
+    }
+
+    /// Returns the target of this element, or of its first linked descendant if it has
+    /// none
+    pub(crate) fn target(&self) -> &str {
+        self.iter()
+            .map(|(_, elem)| elem.url.as_str())
+            .find(|url| !url.is_empty())
+            .unwrap_or("")
+    }
+
+    /// Returns the HTML heading of this element: a link, or a `<span>` if it has no target
+    fn render_heading(&self) -> String {
+        // escape < > symbols by &lt; &gt; using 'encode_text()' in link's Title
+        let title = self
+            .title_markup
+            .as_deref()
+            .map_or_else(|| html_escape::encode_text(&self.title), Cow::Borrowed);
+        if self.is_linked() {
+            format!(
+                "<a href=\"{link}\">{title}</a>",
+                link = html_escape::encode_double_quoted_attribute(&self.url)
+            )
+        } else {
+            format!("<span>{title}</span>")
         }
     }
 
@@ -45,6 +184,15 @@ impl Element {
         self
     }
 
+    /// Excludes this element, and its children, from the numbering of the Toc
+    ///
+    /// This is useful for front matter, e.g. a preface before the first chapter.
+    #[must_use]
+    pub const fn unnumbered(mut self) -> Self {
+        self.numbered = false;
+        self
+    }
+
     /// Change level, recursively, so the structure keeps having some sense
     fn level_up(&mut self, level: i32) {
         self.level = level;
@@ -87,33 +235,107 @@ impl Element {
     ///
     /// See the `add` method of [`Toc`](struct.toc.html).
     pub fn add(&mut self, element: Self) {
-        let mut inserted = false;
-        if let Some(ref mut last_elem) = self.children.last_mut() {
-            if element.level > last_elem.level {
-                last_elem.add(element.clone());
-                inserted = true;
-            }
+        match self.children.last_mut() {
+            Some(last_elem) if element.level > last_elem.level => last_elem.add(element),
+            _ => self.children.push(element),
+        }
+    }
+
+    /// Sets the level of this element, and shifts the level of all its descendants by
+    /// the same amount, so the structure of the subtree is preserved.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use epub_builder::Element;
+    /// let mut elem = Element::new("foo.xhtml", "Foo")
+    ///     .child(Element::new("foo.xhtml#bar", "Bar"));
+    /// elem.relevel(3);
+    /// assert_eq!(elem.children[0].level, 4);
+    /// ```
+    pub fn relevel(&mut self, level: i32) {
+        let delta = level - self.level;
+        self.shift_level(delta);
+    }
+
+    /// Adds `delta` to the level of this element and of all its descendants
+    fn shift_level(&mut self, delta: i32) {
+        self.level += delta;
+        for child in &mut self.children {
+            child.shift_level(delta);
         }
-        if !inserted {
-            self.children.push(element);
+    }
+
+    /// Replaces the url of this element and of all its descendants, if they are linked
+    fn map_urls(&mut self, f: &dyn Fn(&str) -> String) {
+        if self.is_linked() {
+            self.url = f(&self.url);
         }
+        for child in &mut self.children {
+            child.map_urls(f);
+        }
+    }
+
+    /// Iterates over this element and all its descendants, depth-first.
+    ///
+    /// Each element is returned with its depth in the tree, starting at 1 for `self`.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Self)> {
+        depth_first(vec![(1, self)])
+    }
+
+    /// Returns the first element (`self` or one of its descendants, depth-first)
+    /// whose url is `url`
+    #[must_use]
+    pub fn find(&self, url: &str) -> Option<&Self> {
+        self.iter()
+            .map(|(_, elem)| elem)
+            .find(|elem| elem.url == url)
+    }
+
+    /// Depth of this element, i.e. 1 if it has no children, and 1 + the depth of its
+    /// deepest child otherwise
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        1 + self.children.iter().map(Self::depth).max().unwrap_or(0)
     }
 
     /// Render element for Epub's toc.ncx format
     #[doc(hidden)]
     #[must_use]
-    pub fn render_epub(&self, mut offset: u32) -> (u32, String) {
+    pub fn render_epub(&self, offset: u32) -> (u32, String) {
+        self.render_ncx(offset, &mut PlayOrder::new(), None)
+    }
+
+    /// Render element for toc.ncx, using `play_order` to number entries
+    ///
+    /// Children are only rendered up to `max_depth` levels, if it is set.
+    pub(crate) fn render_ncx(
+        &self,
+        mut offset: u32,
+        play_order: &mut PlayOrder,
+        max_depth: Option<usize>,
+    ) -> (u32, String) {
+        let url = self.target();
+        // An element without any target can't be a navPoint, whose content is required
+        if url.is_empty() {
+            return (offset, String::new());
+        }
         offset += 1;
         let id = offset;
-        let children = if self.children.is_empty() {
-            String::new()
-        } else {
-            let mut output: Vec<String> = Vec::new();
+        let order = play_order.get(url);
+        let mut output: Vec<String> = Vec::new();
+        if max_depth != Some(1) {
             for child in &self.children {
-                let (n, s) = child.render_epub(offset);
+                let (n, s) = child.render_ncx(offset, play_order, max_depth.map(|d| d - 1));
                 offset = n;
-                output.push(s);
+                if !s.is_empty() {
+                    output.push(s);
+                }
             }
+        }
+        let children = if output.is_empty() {
+            String::new()
+        } else {
             format!("\n{}", common::indent(output.join("\n"), 1))
         };
         // Try to sanitize the escape title of all HTML elements; if it fails, insert it as is
@@ -122,15 +344,16 @@ impl Element {
             offset,
             format!(
                 "\
-<navPoint id=\"navPoint-{id}\">
+<navPoint id=\"navPoint-{id}\" playOrder=\"{order}\">
   <navLabel>
    <text>{title}</text>
   </navLabel>
   <content src=\"{url}\"/>{children}
 </navPoint>",
                 id = id,
+                order = order,
                 title = escaped_title.trim(),
-                url = self.url,
+                url = html_escape::encode_double_quoted_attribute(url),
                 children = children
             ),
         )
@@ -140,41 +363,89 @@ impl Element {
     #[doc(hidden)]
     #[must_use]
     pub fn render(&self, numbered: bool) -> String {
+        self.render_depth(numbered, false, None)
+    }
+
+    /// Render element as a list element, with children up to `max_depth` levels
+    ///
+    /// If `labelled` is set, the titles already contain their numbers, so the numbers of
+    /// `<ol>` lists are hidden.
+    fn render_depth(&self, numbered: bool, labelled: bool, max_depth: Option<usize>) -> String {
         if self.title.is_empty() {
             return String::new();
         }
-        if self.children.is_empty() {
-            let escaped_title = html_escape::encode_text(&self.title);
-            format!(
-                "<li><a href=\"{link}\">{title}</a></li>",
-                link = self.url,
-                title = escaped_title,
-            )
-        } else {
-            let mut output: Vec<String> = Vec::new();
+        let mut output: Vec<String> = Vec::new();
+        if max_depth != Some(1) {
             for child in &self.children {
-                output.push(child.render(numbered));
+                let s = child.render_depth(numbered, labelled, max_depth.map(|d| d - 1));
+                if !s.is_empty() {
+                    output.push(s);
+                }
+            }
+        }
+        if output.is_empty() {
+            // A `<span>` heading must be followed by a list, so an unlinked element
+            // without any rendered child is skipped
+            if !self.is_linked() {
+                return String::new();
             }
+            format!("<li>{}</li>", self.render_heading())
+        } else {
             let children = format!(
-                "<{oul}>\n{children}\n</{oul}>",
-                oul = if numbered { "ol" } else { "ul" },
+                "{open}\n{children}\n{close}",
+                open = list_open_tag(numbered, labelled),
+                close = if numbered { "</ol>" } else { "</ul>" },
                 children = common::indent(output.join("\n"), 1)
             );
             format!(
                 "\
 <li>
-  <a href=\"{link}\">{title}</a>
+  {heading}
 {children}
 </li>",
-                link = self.url,
-                // escape < > symbols by &lt; &gt; using 'encode_text()' in link's Title
-                title = html_escape::encode_text(&self.title),
+                heading = self.render_heading(),
                 children = common::indent(children, 1)
             )
         }
     }
 }
 
+/// Iterates depth-first over the elements of `stack`, which is in reverse order
+fn depth_first(mut stack: Vec<(usize, &Element)>) -> impl Iterator<Item = (usize, &Element)> {
+    std::iter::from_fn(move || {
+        let (depth, elem) = stack.pop()?;
+        stack.extend(elem.children.iter().rev().map(|child| (depth + 1, child)));
+        Some((depth, elem))
+    })
+}
+
+/// Returns the indices leading to the first element with the given url
+fn position(elements: &[Element], url: &str) -> Option<Vec<usize>> {
+    for (i, elem) in elements.iter().enumerate() {
+        if elem.url == url {
+            return Some(vec![i]);
+        }
+        if let Some(mut path) = position(&elem.children, url) {
+            path.insert(0, i);
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// Returns the children of the first element with the given url, or nothing if there is
+/// no such element
+fn children_of<'a>(elements: &'a [Element], url: &str) -> &'a [Element] {
+    let Some(path) = position(elements, url) else {
+        return &[];
+    };
+    let mut siblings = elements;
+    for i in path {
+        siblings = &siblings[i].children;
+    }
+    siblings
+}
+
 /// A Table Of Contents
 ///
 /// It basically contains a list of [`Element`](struct.Element.html)s.
@@ -198,17 +469,113 @@ impl Element {
 ///    // render the toc (non-numbered list) and returns a string
 ///    .render(false);
 /// ```
-#[derive(Debug, Default)]
+///
+/// A Toc can't be built with a struct literal, since it has private fields: use `new`
+/// or `default`.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
 pub struct Toc {
     /// The elements composing the TOC
     pub elements: Vec<Element>,
+    /// Maximum number of levels rendered
+    max_depth: Option<usize>,
+    /// Numbering of the labels
+    numbering: Option<Numbering>,
 }
 
 impl Toc {
     /// Creates a new, empty, Toc
     #[must_use]
     pub const fn new() -> Self {
-        Self { elements: vec![] }
+        Self {
+            elements: vec![],
+            max_depth: None,
+            numbering: None,
+        }
+    }
+
+    /// Builds a Toc from the `toc` nav element of an existing EPUB 3 navigation document
+    /// (nav.xhtml), e.g. to rebuild the package of a book.
+    ///
+    /// The hierarchy, hrefs and labels are kept: hrefs are left as they are, i.e.
+    /// relative to the navigation document, and labels with markup keep it in
+    /// `title_markup`. Headings without a link (`<span>`) give unlinked elements.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document has no `toc` nav element.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use epub_builder::Toc;
+    /// let nav = r#"<html xmlns:epub="http://www.idpf.org/2007/ops"><body>
+    ///   <nav epub:type="toc"><ol>
+    ///     <li><a href="chapter_1.xhtml">Chapter 1</a></li>
+    ///   </ol></nav>
+    /// </body></html>"#;
+    /// let toc = Toc::from_nav(nav).unwrap();
+    /// assert_eq!(toc.elements[0].title, "Chapter 1");
+    /// ```
+    pub fn from_nav(xhtml: &str) -> Result<Self> {
+        toc_import::from_nav(xhtml)
+    }
+
+    /// Builds a Toc from the `navMap` of an existing toc.ncx document, e.g. to rebuild
+    /// the package of a book.
+    ///
+    /// The hierarchy, hrefs and labels are kept; hrefs are left as they are, i.e.
+    /// relative to the NCX document.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document has no `navMap` element.
+    pub fn from_ncx(ncx: &str) -> Result<Self> {
+        toc_import::from_ncx(ncx)
+    }
+
+    /// Sets the maximum number of levels that are rendered, both for toc.ncx and for
+    /// HTML. Deeper elements are kept in the Toc, but not displayed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use epub_builder::{Toc, Element};
+    /// let mut toc = Toc::new();
+    /// toc.add(Element::new("chapter_1.xhtml", "Chapter 1")
+    ///           .child(Element::new("chapter_1.xhtml#1", "Section 1")));
+    /// // Only render chapters
+    /// toc.max_depth(1);
+    /// assert_eq!(toc.depth(), 1);
+    /// ```
+    pub fn max_depth(&mut self, depth: usize) -> &mut Self {
+        self.max_depth = Some(depth.max(1));
+        self
+    }
+
+    /// Sets how the labels of the elements are numbered when the Toc is rendered
+    ///
+    /// See [`Numbering`](enum.Numbering.html).
+    pub fn numbering(&mut self, numbering: Numbering) -> &mut Self {
+        self.numbering = Some(numbering);
+        self
+    }
+
+    /// Returns the elements to render, with their numbers if needed
+    fn rendered_elements(&self) -> Cow<'_, [Element]> {
+        self.numbering
+            .as_ref()
+            .map_or(Cow::Borrowed(self.elements.as_slice()), |numbering| {
+                Cow::Owned(numbering.apply(&self.elements))
+            })
+    }
+
+    /// Returns the depth of the rendered Toc, i.e. the number of levels of its deepest
+    /// element, within the limit set by `max_depth`. The depth is at least 1.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        let depth = self.elements.iter().map(Element::depth).max().unwrap_or(1);
+        self.max_depth.map_or(depth, |max| depth.min(max))
     }
 
     /// Returns `true` if the toc is empty, `false` else.
@@ -216,7 +583,7 @@ impl Toc {
     /// Note that `empty` here means that the the toc has zero *or one*
     /// element, since it's still not worth displaying it in this case.
     #[must_use]
-    pub fn is_empty(&self) -> bool {
+    pub const fn is_empty(&self) -> bool {
         self.elements.len() <= 1
     }
 
@@ -242,50 +609,295 @@ impl Toc {
     /// There are some cases where this behaviour might not be what you want; however,
     /// it makes sure that the TOC can still be renderer correctly for HTML and EPUB.
     pub fn add(&mut self, element: Element) -> &mut Self {
-        let mut inserted = false;
-        if let Some(ref mut last_elem) = self.elements.last_mut() {
-            if element.level > last_elem.level {
-                last_elem.add(element.clone());
-                inserted = true;
-            }
+        match self.elements.last_mut() {
+            Some(last_elem) if element.level > last_elem.level => last_elem.add(element),
+            _ => self.elements.push(element),
+        }
+        self
+    }
+
+    /// Iterates over all the elements of the Toc, depth-first.
+    ///
+    /// Each element is returned with its depth in the tree, starting at 1 for top-level
+    /// elements.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use epub_builder::{Toc, Element};
+    /// let mut toc = Toc::new();
+    /// toc.add(Element::new("chapter_1.xhtml", "Chapter 1"))
+    ///    .add(Element::new("chapter_1.xhtml#1", "Section 1").level(2))
+    ///    .add(Element::new("chapter_2.xhtml", "Chapter 2"));
+    /// let titles: Vec<_> = toc.iter().map(|(depth, elem)| (depth, elem.title.as_str())).collect();
+    /// assert_eq!(titles, [(1, "Chapter 1"), (2, "Section 1"), (1, "Chapter 2")]);
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Element)> {
+        depth_first(self.elements.iter().rev().map(|elem| (1, elem)).collect())
+    }
+
+    /// Returns the first element (depth-first) whose url is `url`
+    #[must_use]
+    pub fn find(&self, url: &str) -> Option<&Element> {
+        self.iter()
+            .map(|(_, elem)| elem)
+            .find(|elem| elem.url == url)
+    }
+
+    /// Returns a mutable reference to the first element (depth-first) whose url is `url`
+    pub fn find_mut(&mut self, url: &str) -> Option<&mut Element> {
+        let path = position(&self.elements, url)?;
+        let (first, rest) = path.split_first()?;
+        let mut elem = &mut self.elements[*first];
+        for i in rest {
+            elem = &mut elem.children[*i];
         }
-        if !inserted {
-            self.elements.push(element);
+        Some(elem)
+    }
+
+    /// Returns the list containing the element at `path`, and its index in this list
+    fn siblings_mut(&mut self, path: &[usize]) -> (&mut Vec<Element>, usize) {
+        let (index, parents) = path.split_last().expect("path is never empty");
+        let mut siblings = &mut self.elements;
+        for i in parents {
+            siblings = &mut siblings[*i].children;
         }
+        (siblings, *index)
+    }
+
+    /// Removes the first element (depth-first) whose url is `url`, with its children,
+    /// and returns it.
+    pub fn remove(&mut self, url: &str) -> Option<Element> {
+        let path = position(&self.elements, url)?;
+        let (siblings, index) = self.siblings_mut(&path);
+        Some(siblings.remove(index))
+    }
 
+    /// Removes the elements (with their children) for which `f` returns `false`
+    ///
+    /// # Example
+    ///
+    /// Drop all entries below level 2:
+    ///
+    /// ```
+    /// # use epub_builder::{Toc, Element};
+    /// let mut toc = Toc::new();
+    /// toc.add(Element::new("chapter_1.xhtml", "Chapter 1")
+    ///           .child(Element::new("chapter_1.xhtml#1", "Section 1")
+    ///               .child(Element::new("chapter_1.xhtml#1.1", "Section 1.1"))));
+    /// toc.retain(|elem| elem.level <= 2);
+    /// assert!(toc.find("chapter_1.xhtml#1").is_some());
+    /// assert!(toc.find("chapter_1.xhtml#1.1").is_none());
+    /// ```
+    pub fn retain<F: FnMut(&Element) -> bool>(&mut self, mut f: F) -> &mut Self {
+        fn retain_in<F: FnMut(&Element) -> bool>(elements: &mut Vec<Element>, f: &mut F) {
+            elements.retain(|elem| f(elem));
+            for elem in elements {
+                retain_in(&mut elem.children, f);
+            }
+        }
+        retain_in(&mut self.elements, &mut f);
         self
     }
 
+    /// Inserts `element` just before the first element whose url is `url`, as its
+    /// sibling. The level of `element` (and of its children) is adjusted accordingly.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no element with this url.
+    pub fn insert_before(&mut self, url: &str, element: Element) -> Result<&mut Self> {
+        self.insert_sibling(url, element, 0)
+    }
+
+    /// Inserts `element` just after the first element whose url is `url`, as its
+    /// sibling. The level of `element` (and of its children) is adjusted accordingly.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no element with this url.
+    pub fn insert_after(&mut self, url: &str, element: Element) -> Result<&mut Self> {
+        self.insert_sibling(url, element, 1)
+    }
+
+    /// Inserts `element` at `offset` from the element whose url is `url`
+    fn insert_sibling(
+        &mut self,
+        url: &str,
+        mut element: Element,
+        offset: usize,
+    ) -> Result<&mut Self> {
+        let Some(path) = position(&self.elements, url) else {
+            bail!("no element with url '{url}' in the toc");
+        };
+        let (siblings, index) = self.siblings_mut(&path);
+        element.relevel(siblings[index].level);
+        siblings.insert(index + offset, element);
+        Ok(self)
+    }
+
+    /// Inserts `element` as the last child of the first element whose url is `url`.
+    /// The level of `element` (and of its children) is adjusted accordingly.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no element with this url.
+    pub fn insert_into(&mut self, url: &str, mut element: Element) -> Result<&mut Self> {
+        let Some(parent) = self.find_mut(url) else {
+            bail!("no element with url '{url}' in the toc");
+        };
+        element.relevel(parent.level + 1);
+        parent.children.push(element);
+        Ok(self)
+    }
+
+    /// Moves the element whose url is `url` (with its children) just before the element
+    /// whose url is `target`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either element can't be found, or if `target` is part of the
+    /// moved subtree. The Toc is left unchanged in this case.
+    pub fn move_before(&mut self, url: &str, target: &str) -> Result<&mut Self> {
+        let element = self.take_for_move(url, target)?;
+        self.insert_before(target, element)
+    }
+
+    /// Moves the element whose url is `url` (with its children) just after the element
+    /// whose url is `target`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either element can't be found, or if `target` is part of the
+    /// moved subtree. The Toc is left unchanged in this case.
+    pub fn move_after(&mut self, url: &str, target: &str) -> Result<&mut Self> {
+        let element = self.take_for_move(url, target)?;
+        self.insert_after(target, element)
+    }
+
+    /// Moves the element whose url is `url` (with its children) to be the last child
+    /// of the element whose url is `target`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either element can't be found, or if `target` is part of the
+    /// moved subtree. The Toc is left unchanged in this case.
+    ///
+    /// # Example
+    ///
+    /// Merge two parts:
+    ///
+    /// ```
+    /// # use epub_builder::{Toc, Element};
+    /// let mut toc = Toc::new();
+    /// toc.add(Element::new("part_1.xhtml", "Part 1")
+    ///           .child(Element::new("chapter_1.xhtml", "Chapter 1")))
+    ///    .add(Element::new("part_2.xhtml", "Part 2")
+    ///           .child(Element::new("chapter_2.xhtml", "Chapter 2")));
+    /// toc.move_into("chapter_2.xhtml", "part_1.xhtml").unwrap();
+    /// toc.remove("part_2.xhtml");
+    /// assert_eq!(toc.elements.len(), 1);
+    /// assert_eq!(toc.elements[0].children.len(), 2);
+    /// ```
+    pub fn move_into(&mut self, url: &str, target: &str) -> Result<&mut Self> {
+        let element = self.take_for_move(url, target)?;
+        self.insert_into(target, element)
+    }
+
+    /// Removes the element to be moved next to (or into) `target`, after checking
+    /// that the move is possible
+    fn take_for_move(&mut self, url: &str, target: &str) -> Result<Element> {
+        let Some(element) = self.find(url) else {
+            bail!("no element with url '{url}' in the toc");
+        };
+        if element.find(target).is_some() {
+            bail!("can't move '{url}' relative to '{target}', which is part of it");
+        }
+        if self.find(target).is_none() {
+            bail!("no element with url '{target}' in the toc");
+        }
+        Ok(self.remove(url).expect("element was found"))
+    }
+
     /// Render the Toc in a toc.ncx compatible way, for EPUB.
     pub fn render_epub(&mut self) -> String {
+        self.render_ncx(&mut PlayOrder::new())
+    }
+
+    /// Replaces the urls of all the elements, e.g. to make them relative to another
+    /// directory
+    pub(crate) fn map_urls(&mut self, f: &dyn Fn(&str) -> String) {
+        for elem in &mut self.elements {
+            elem.map_urls(f);
+        }
+    }
+
+    /// Render the Toc for toc.ncx, using `play_order` to number entries
+    pub(crate) fn render_ncx(&self, play_order: &mut PlayOrder) -> String {
         let mut output: Vec<String> = Vec::new();
         let mut offset = 0;
-        for elem in &self.elements {
-            let (n, s) = elem.render_epub(offset);
+        for elem in self.rendered_elements().iter() {
+            let (n, s) = elem.render_ncx(offset, play_order, self.max_depth);
             offset = n;
-            output.push(s);
+            if !s.is_empty() {
+                output.push(s);
+            }
         }
         common::indent(output.join("\n"), 2)
     }
 
     /// Render the Toc in either <ul> or <ol> form (according to numbered)
     pub fn render(&mut self, numbered: bool) -> String {
+        self.render_part(None, None, numbered)
+    }
+
+    /// Render the Toc, or only the children of the element whose url is `root`, with at
+    /// most `max_depth` levels (within the limit set by the `max_depth` method)
+    ///
+    /// Numbers, if any, are the same as in the whole Toc.
+    pub(crate) fn render_part(
+        &self,
+        root: Option<&str>,
+        max_depth: Option<usize>,
+        numbered: bool,
+    ) -> String {
+        let all = self.rendered_elements();
+        let elements = root.map_or(&all[..], |url| children_of(&all, url));
+        let max_depth = match (self.max_depth, max_depth) {
+            (Some(a), Some(b)) => Some(a.min(b).max(1)),
+            (a, b) => a.or(b).map(|d| d.max(1)),
+        };
+        let labelled = self.numbering.is_some();
         let mut output: Vec<String> = Vec::new();
-        for elem in &self.elements {
-            log::debug!("rendered elem: {:?}", &elem.render(numbered));
-            output.push(elem.render(numbered));
+        for elem in elements {
+            let rendered = elem.render_depth(numbered, labelled, max_depth);
+            log::debug!("rendered elem: {:?}", &rendered);
+            if !rendered.is_empty() {
+                output.push(rendered);
+            }
         }
         common::indent(
             format!(
-                "<{oul}>\n{output}\n</{oul}>",
+                "{open}\n{output}\n{close}",
                 output = common::indent(output.join("\n"), 1),
-                oul = if numbered { "ol" } else { "ul" }
+                open = list_open_tag(numbered, labelled),
+                close = if numbered { "</ol>" } else { "</ul>" }
             ),
             2,
         )
     }
 }
 
+/// Opening tag of a list of the toc: `<ol>` if it is `numbered`, without visible
+/// numbers if the titles are already `labelled` by a `Numbering`, and `<ul>` otherwise
+const fn list_open_tag(numbered: bool, labelled: bool) -> &'static str {
+    match (numbered, labelled) {
+        (false, _) => "<ul>",
+        (true, false) => "<ol>",
+        (true, true) => "<ol style=\"list-style: none\">",
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////////
 ///                                  TESTS                                     //
 /////////////////////////////////////////////////////////////////////////////////
@@ -319,13 +931,13 @@ fn toc_epub_simple() {
     toc.add(Element::new("#1", "1"));
     toc.add(Element::new("#2", "2"));
     let actual = toc.render_epub();
-    let expected = "    <navPoint id=\"navPoint-1\">
+    let expected = "    <navPoint id=\"navPoint-1\" playOrder=\"1\">
       <navLabel>
        <text>1</text>
       </navLabel>
       <content src=\"#1\"/>
     </navPoint>
-    <navPoint id=\"navPoint-2\">
+    <navPoint id=\"navPoint-2\" playOrder=\"2\">
       <navLabel>
        <text>2</text>
       </navLabel>
@@ -342,24 +954,24 @@ fn toc_epub_simple_sublevels() {
     toc.add(Element::new("#2", "2"));
     toc.add(Element::new("#2.1", "2.1").level(2));
     let actual = toc.render_epub();
-    let expected = "    <navPoint id=\"navPoint-1\">
+    let expected = "    <navPoint id=\"navPoint-1\" playOrder=\"1\">
       <navLabel>
        <text>1</text>
       </navLabel>
       <content src=\"#1\"/>
-      <navPoint id=\"navPoint-2\">
+      <navPoint id=\"navPoint-2\" playOrder=\"2\">
         <navLabel>
          <text>1.1</text>
         </navLabel>
         <content src=\"#1.1\"/>
       </navPoint>
     </navPoint>
-    <navPoint id=\"navPoint-3\">
+    <navPoint id=\"navPoint-3\" playOrder=\"3\">
       <navLabel>
        <text>2</text>
       </navLabel>
       <content src=\"#2\"/>
-      <navPoint id=\"navPoint-4\">
+      <navPoint id=\"navPoint-4\" playOrder=\"4\">
         <navLabel>
          <text>2.1</text>
         </navLabel>
@@ -376,18 +988,18 @@ fn toc_epub_broken_sublevels() {
     toc.add(Element::new("#2", "2"));
     toc.add(Element::new("#2.1", "2.1").level(2));
     let actual = toc.render_epub();
-    let expected = "    <navPoint id=\"navPoint-1\">
+    let expected = "    <navPoint id=\"navPoint-1\" playOrder=\"1\">
       <navLabel>
        <text>1.1</text>
       </navLabel>
       <content src=\"#1.1\"/>
     </navPoint>
-    <navPoint id=\"navPoint-2\">
+    <navPoint id=\"navPoint-2\" playOrder=\"2\">
       <navLabel>
        <text>2</text>
       </navLabel>
       <content src=\"#2\"/>
-      <navPoint id=\"navPoint-3\">
+      <navPoint id=\"navPoint-3\" playOrder=\"3\">
         <navLabel>
          <text>2.1</text>
         </navLabel>
@@ -402,7 +1014,7 @@ fn toc_epub_title_escaped() {
     let mut toc = Toc::new();
     toc.add(Element::new("#1", "D&D"));
     let actual = toc.render_epub();
-    let expected = "    <navPoint id=\"navPoint-1\">
+    let expected = "    <navPoint id=\"navPoint-1\" playOrder=\"1\">
       <navLabel>
        <text>D&amp;D</text>
       </navLabel>
@@ -410,3 +1022,161 @@ fn toc_epub_title_escaped() {
     </navPoint>";
     assert_eq!(&actual, expected);
 }
+
+#[test]
+fn toc_epub_shared_play_order() {
+    let mut toc = Toc::new();
+    toc.add(Element::new("ch1.xhtml", "Part 1"));
+    toc.add(Element::new("ch1.xhtml", "Chapter 1").level(2));
+    toc.add(Element::new("ch2.xhtml", "Chapter 2").level(2));
+    let actual = toc.render_epub();
+    assert!(actual.contains("<navPoint id=\"navPoint-1\" playOrder=\"1\">"));
+    assert!(actual.contains("<navPoint id=\"navPoint-2\" playOrder=\"1\">"));
+    assert!(actual.contains("<navPoint id=\"navPoint-3\" playOrder=\"2\">"));
+}
+
+#[test]
+fn toc_max_depth() {
+    let mut toc = Toc::new();
+    toc.add(Element::new("#1", "1"));
+    toc.add(Element::new("#1.1", "1.1").level(2));
+    toc.add(Element::new("#1.1.1", "1.1.1").level(3));
+    assert_eq!(toc.depth(), 3);
+    toc.max_depth(2);
+    assert_eq!(toc.depth(), 2);
+    let actual = toc.render(false);
+    let expected = "    <ul>
+      <li>
+        <a href=\"#1\">1</a>
+        <ul>
+          <li><a href=\"#1.1\">1.1</a></li>
+        </ul>
+      </li>
+    </ul>";
+    assert_eq!(&actual, expected);
+    let actual = toc.render_epub();
+    assert!(actual.contains("#1.1"));
+    assert!(!actual.contains("#1.1.1"));
+}
+
+#[test]
+fn toc_tree_edit() {
+    let mut toc = Toc::new();
+    toc.add(Element::new("a.xhtml", "A").child(Element::new("a.xhtml#1", "A.1")))
+        .add(Element::new("b.xhtml", "B"))
+        .add(Element::new("b.xhtml#1", "B.1").level(2));
+
+    toc.insert_before("b.xhtml#1", Element::new("b.xhtml#0", "B.0"))
+        .unwrap()
+        .insert_after("a.xhtml", Element::new("a2.xhtml", "A2").level(5))
+        .unwrap();
+    assert_eq!(toc.find("a2.xhtml").unwrap().level, 1);
+    assert_eq!(toc.find("b.xhtml#0").unwrap().level, 2);
+
+    toc.move_into("b.xhtml", "a.xhtml#1").unwrap();
+    let entries: Vec<_> = toc
+        .iter()
+        .map(|(depth, elem)| (depth, elem.level, elem.title.as_str()))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            (1, 1, "A"),
+            (2, 2, "A.1"),
+            (3, 3, "B"),
+            (4, 4, "B.0"),
+            (4, 4, "B.1"),
+            (1, 1, "A2"),
+        ]
+    );
+
+    assert!(toc.move_before("a.xhtml", "b.xhtml").is_err());
+    assert!(toc.move_after("missing.xhtml", "a.xhtml").is_err());
+    assert!(toc
+        .insert_into("missing.xhtml", Element::new("c", "C"))
+        .is_err());
+    assert_eq!(toc.iter().count(), 6);
+
+    let removed = toc.remove("a.xhtml#1").unwrap();
+    assert_eq!(removed.iter().count(), 4);
+    assert_eq!(toc.iter().count(), 2);
+    assert!(toc.remove("a.xhtml#1").is_none());
+}
+
+#[test]
+fn toc_unlinked() {
+    let mut toc = Toc::new();
+    toc.add(Element::new("intro.xhtml", "Intro"));
+    toc.add(Element::unlinked("Part <1>").child(Element::new("ch1.xhtml", "Chapter 1")));
+    let actual = toc.render(true);
+    let expected = "    <ol>
+      <li><a href=\"intro.xhtml\">Intro</a></li>
+      <li>
+        <span>Part &lt;1&gt;</span>
+        <ol>
+          <li><a href=\"ch1.xhtml\">Chapter 1</a></li>
+        </ol>
+      </li>
+    </ol>";
+    assert_eq!(&actual, expected);
+    let actual = toc.render_epub();
+    assert!(actual.contains(
+        "<navPoint id=\"navPoint-2\" playOrder=\"2\">
+      <navLabel>
+       <text>Part &lt;1&gt;</text>
+      </navLabel>
+      <content src=\"ch1.xhtml\"/>"
+    ));
+    assert!(actual.contains("<navPoint id=\"navPoint-3\" playOrder=\"2\">"));
+
+    // Without any linked descendant, there is no target for the navPoint
+    toc.add(Element::unlinked("Part 2").child(Element::unlinked("Chapter 2")));
+    let actual = toc.render_epub();
+    assert!(!actual.contains("Part 2"));
+    assert!(!actual.contains("<content src=\"\"/>"));
+    assert!(actual.ends_with("<content src=\"ch1.xhtml\"/>\n      </navPoint>\n    </navPoint>"));
+    // nor for a `<span>` heading, which must be followed by a list
+    assert!(!toc.render(true).contains("Part 2"));
+    assert!(!toc.render(true).contains("Chapter 2"));
+    toc.add(Element::unlinked("Part 3"));
+    assert!(!toc.render(true).contains("Part 3"));
+    assert!(!toc.render_epub().contains("Part 3"));
+    assert!(toc.render(true).ends_with("      </li>\n    </ol>"));
+
+    // Same if its children are not rendered
+    toc.max_depth(1);
+    assert!(!toc.render(true).contains("<span>"));
+    assert!(toc.render_epub().contains("<text>Part &lt;1&gt;</text>"));
+}
+
+#[test]
+fn toc_title_markup() {
+    let mut toc = Toc::new();
+    toc.add(Element::new("#1", "").title_markup("The <em>Great</em> &amp; Co"));
+    assert!(toc
+        .render(false)
+        .contains("<li><a href=\"#1\">The <em>Great</em> &amp; Co</a></li>"));
+    assert!(toc
+        .render_epub()
+        .contains("<text>The Great &amp; Co</text>"));
+}
+
+#[test]
+fn toc_render_part() {
+    let mut toc = Toc::new();
+    toc.numbering(Numbering::Decimal);
+    toc.add(Element::new("p1.xhtml", "Part 1"));
+    toc.add(
+        Element::new("p2.xhtml", "Part 2").child(
+            Element::new("ch1.xhtml", "Chapter").child(Element::new("ch1.xhtml#1", "Section")),
+        ),
+    );
+    let actual = toc.render_part(Some("p2.xhtml"), Some(1), false);
+    let expected = "    <ul>
+      <li><a href=\"ch1.xhtml\">2.1 Chapter</a></li>
+    </ul>";
+    assert_eq!(&actual, expected);
+    assert!(!toc
+        .render_part(Some("missing.xhtml"), None, false)
+        .contains("<li>"));
+}
@@ -0,0 +1,319 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::common;
+use crate::Element;
+use crate::Result;
+use crate::Toc;
+
+use color_eyre::eyre::bail;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Matches a tag, a comment or a declaration
+static TAG: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?s)<!--.*?-->|<[?!][^>]*>|<(/)?([A-Za-z][\w:.-]*)((?:[^>"']|"[^"]*"|'[^']*')*?)(/)?>"#,
+    )
+    .expect("error compiling tag regex")
+});
+
+/// Matches an attribute of a tag
+static ATTRIBUTE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"([\w:.-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#)
+        .expect("error compiling attribute regex")
+});
+
+/// A tag of a XML document
+struct Tag<'a> {
+    /// Local name of the element, without namespace prefix
+    name: &'a str,
+    /// Attributes, as they appear in the document
+    attributes: &'a str,
+    /// `true` for an end tag
+    end: bool,
+    /// `true` for an empty element tag
+    empty: bool,
+    /// Position of the start of the tag in the document
+    start: usize,
+    /// Position of the end of the tag in the document
+    stop: usize,
+}
+
+impl Tag<'_> {
+    /// Returns the (entity-decoded) value of an attribute
+    fn attribute(&self, name: &str) -> Option<String> {
+        ATTRIBUTE
+            .captures_iter(self.attributes)
+            .find(|caps| &caps[1] == name)
+            .and_then(|caps| caps.get(2).or_else(|| caps.get(3)))
+            .map(|m| html_escape::decode_html_entities(m.as_str()).into_owned())
+    }
+
+    /// Returns `true` if this is a start tag of an element named `name`
+    fn opens(&self, name: &str) -> bool {
+        !self.end && self.name == name
+    }
+
+    /// Returns `true` if this is an end tag of an element named `name`
+    fn closes(&self, name: &str) -> bool {
+        self.end && self.name == name
+    }
+}
+
+/// Iterates over the tags of a document, ignoring comments and declarations
+fn tags(xml: &str) -> impl Iterator<Item = Tag<'_>> {
+    TAG.captures_iter(xml).filter_map(|caps| {
+        let whole = caps.get(0)?;
+        let name = caps.get(2)?.as_str();
+        Some(Tag {
+            name: name.rsplit(':').next().unwrap_or(name),
+            attributes: caps.get(3).map_or("", |m| m.as_str()),
+            end: caps.get(1).is_some(),
+            empty: caps.get(4).is_some(),
+            start: whole.start(),
+            stop: whole.end(),
+        })
+    })
+}
+
+/// Adds `element` as the last child of the top of `stack`, or to `toc` if the stack
+/// is empty
+fn attach(toc: &mut Toc, stack: &mut [Element], element: Element) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(element),
+        None => toc.elements.push(element),
+    }
+}
+
+/// Builds a Toc from the `toc` nav element of an EPUB 3 navigation document
+pub fn from_nav(xhtml: &str) -> Result<Toc> {
+    let mut toc = Toc::new();
+    let mut stack: Vec<Element> = vec![];
+    let mut in_toc = false;
+    let mut navs = 0;
+    // Start of the label being read, its element name and nesting
+    let mut label: Option<(usize, &str, usize)> = None;
+    for tag in tags(xhtml) {
+        if !in_toc {
+            let is_toc = tag.opens("nav")
+                && tag
+                    .attribute("epub:type")
+                    .is_some_and(|t| t.split_whitespace().any(|t| t == "toc"));
+            if is_toc {
+                in_toc = true;
+                navs = 1;
+            }
+            continue;
+        }
+        if let Some((start, name, nesting)) = label {
+            if tag.opens(name) && !tag.empty {
+                label = Some((start, name, nesting + 1));
+            } else if tag.closes(name) {
+                if nesting > 0 {
+                    label = Some((start, name, nesting - 1));
+                    continue;
+                }
+                let markup = xhtml[start..tag.start].trim();
+                if let Some(elem) = stack.last_mut() {
+                    elem.title = common::plain_text(markup);
+                    if markup.contains('<') {
+                        elem.title_markup = Some(markup.to_owned());
+                    }
+                }
+                label = None;
+            }
+            continue;
+        }
+        match tag.name {
+            "nav" if tag.end => {
+                navs -= 1;
+                if navs == 0 {
+                    break;
+                }
+            }
+            "nav" if !tag.empty => navs += 1,
+            "li" if tag.end => {
+                if let Some(element) = stack.pop() {
+                    attach(&mut toc, &mut stack, element);
+                }
+            }
+            "li" if !tag.empty => {
+                let level = i32::try_from(stack.len() + 1).unwrap_or(i32::MAX);
+                stack.push(Element::new("", "").level(level));
+            }
+            "a" | "span" if !tag.end && !tag.empty => {
+                if let Some(elem) = stack.last_mut() {
+                    if elem.title.is_empty() {
+                        if tag.name == "a" {
+                            elem.url = tag.attribute("href").unwrap_or_default();
+                        }
+                        label = Some((tag.stop, tag.name, 0));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if !in_toc {
+        bail!("no nav element with epub:type=\"toc\" in navigation document");
+    }
+    while let Some(element) = stack.pop() {
+        attach(&mut toc, &mut stack, element);
+    }
+    Ok(toc)
+}
+
+/// Builds a Toc from the `navMap` of a NCX document
+pub fn from_ncx(ncx: &str) -> Result<Toc> {
+    let mut toc = Toc::new();
+    let mut stack: Vec<Element> = vec![];
+    let mut in_map = false;
+    // Start of the text of the label being read
+    let mut text: Option<usize> = None;
+    for tag in tags(ncx) {
+        if !in_map {
+            if tag.opens("navMap") {
+                in_map = true;
+            }
+            continue;
+        }
+        match tag.name {
+            "navMap" if tag.end => break,
+            "navPoint" if tag.end => {
+                if let Some(element) = stack.pop() {
+                    attach(&mut toc, &mut stack, element);
+                }
+            }
+            "navPoint" if !tag.empty => {
+                let level = i32::try_from(stack.len() + 1).unwrap_or(i32::MAX);
+                stack.push(Element::new("", "").level(level));
+            }
+            "text" if !tag.end && !tag.empty => text = Some(tag.stop),
+            "text" if tag.end => {
+                if let (Some(start), Some(elem)) = (text.take(), stack.last_mut()) {
+                    if elem.title.is_empty() {
+                        elem.title = common::plain_text(&ncx[start..tag.start]);
+                    }
+                }
+            }
+            "content" if !tag.end => {
+                if let Some(elem) = stack.last_mut() {
+                    if elem.url.is_empty() {
+                        elem.url = tag.attribute("src").unwrap_or_default();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if !in_map {
+        bail!("no navMap element in NCX document");
+    }
+    while let Some(element) = stack.pop() {
+        attach(&mut toc, &mut stack, element);
+    }
+    Ok(toc)
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+///                                  TESTS                                     //
+/////////////////////////////////////////////////////////////////////////////////
+
+#[test]
+fn toc_import_nav() {
+    let nav = r#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<body>
+  <nav epub:type="landmarks"><ol><li><a href="cover.xhtml">Cover</a></li></ol></nav>
+  <nav epub:type="toc" id="toc">
+    <h1>Contents</h1>
+    <!-- <li><a href="commented.xhtml">Commented</a></li> -->
+    <ol>
+      <li><a href="intro.xhtml">Intro &amp; more</a></li>
+      <li>
+        <span>Part <em>One</em></span>
+        <ol>
+          <li><a href="ch1.xhtml">Chapter 1</a>
+            <ol><li><a href="ch1.xhtml#s1">Section</a></li></ol>
+          </li>
+        </ol>
+      </li>
+    </ol>
+  </nav>
+</body>
+</html>"#;
+    let toc = from_nav(nav).unwrap();
+    let entries: Vec<_> = toc
+        .iter()
+        .map(|(depth, elem)| (depth, elem.level, elem.url.as_str(), elem.title.as_str()))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            (1, 1, "intro.xhtml", "Intro & more"),
+            (1, 1, "", "Part One"),
+            (2, 2, "ch1.xhtml", "Chapter 1"),
+            (3, 3, "ch1.xhtml#s1", "Section"),
+        ]
+    );
+    assert_eq!(toc.elements[0].title_markup, None);
+    assert_eq!(
+        toc.elements[1].title_markup.as_deref(),
+        Some("Part <em>One</em>")
+    );
+
+    assert!(from_nav("<html><nav epub:type=\"landmarks\"></nav></html>").is_err());
+
+    // Hrefs are decoded when imported, and escaped again when rendered
+    let mut toc = from_nav(
+        r#"<nav epub:type="toc"><ol><li><a href="a&amp;&quot;b.xhtml">A</a></li></ol></nav>"#,
+    )
+    .unwrap();
+    assert_eq!(toc.elements[0].url, "a&\"b.xhtml");
+    assert!(toc
+        .render(false)
+        .contains("<a href=\"a&amp;&quot;b.xhtml\">A</a>"));
+    assert!(toc
+        .render_epub()
+        .contains("<content src=\"a&amp;&quot;b.xhtml\"/>"));
+}
+
+#[test]
+fn toc_import_ncx() {
+    let ncx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <docTitle><text>Book</text></docTitle>
+  <navMap>
+    <navPoint id="navPoint-1" playOrder="1">
+      <navLabel><text>Chapter 1 &lt;draft&gt;</text></navLabel>
+      <content src="ch1.xhtml"/>
+      <navPoint id="navPoint-2" playOrder="2">
+        <navLabel><text>Section</text></navLabel>
+        <content src="ch1.xhtml#s1"/>
+      </navPoint>
+    </navPoint>
+    <navPoint id="navPoint-3" playOrder="3">
+      <navLabel><text>Chapter 2</text></navLabel>
+      <content src="ch2.xhtml"/>
+    </navPoint>
+  </navMap>
+  <pageList><pageTarget id="p1"><navLabel><text>1</text></navLabel><content src="ch1.xhtml#p1"/></pageTarget></pageList>
+</ncx>"#;
+    let toc = from_ncx(ncx).unwrap();
+    let entries: Vec<_> = toc
+        .iter()
+        .map(|(depth, elem)| (depth, elem.url.as_str(), elem.title.as_str()))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            (1, "ch1.xhtml", "Chapter 1 <draft>"),
+            (2, "ch1.xhtml#s1", "Section"),
+            (1, "ch2.xhtml", "Chapter 2"),
+        ]
+    );
+
+    assert!(from_ncx("<ncx></ncx>").is_err());
+}
@@ -4,18 +4,31 @@
 
 use crate::Result;
 
+use color_eyre::eyre::bail;
 use std::io::Read;
 use std::io::Write;
 use std::path::Path;
 
 /// An abstraction over possible Zip implementations.
 ///
-/// The actual implementations are `ZipCommand` (uses the system command zip) or
-/// `ZipLibrary` (uses the [Rust zip library](https://crates.io/crates/zip)).
+/// The actual implementations are `ZipCommand` (uses the system command zip),
+/// `ZipLibrary` (uses the [Rust zip library](https://crates.io/crates/zip)) or
+/// `ZipStream` (uses the same library, but writes the archive as files are added).
 pub trait Zip {
     /// Write the source content to a file in the archive
     fn write_file<P: AsRef<Path>, R: Read>(&mut self, file: P, content: R) -> Result<()>;
 
     /// Generate the ZIP file
     fn generate<W: Write>(&mut self, _: W) -> Result<()>;
+
+    /// Whether the ZIP file is written into a destination of its own as files are
+    /// added, in which case it is completed with `finish` instead of `generate`
+    fn is_streamed(&self) -> bool {
+        false
+    }
+
+    /// Complete the ZIP file, for implementations writing it as files are added
+    fn finish(&mut self) -> Result<()> {
+        bail!("this zip implementation needs a writer: use `generate`")
+    }
 }
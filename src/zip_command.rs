@@ -91,7 +91,7 @@ impl ZipCommand {
     }
 
     /// Adds a file to the temporary directory
-    fn add_to_tmp_dir<P: AsRef<Path>, R: Read>(&mut self, path: P, mut content: R) -> Result<()> {
+    fn add_to_tmp_dir<P: AsRef<Path>, R: Read>(&self, path: P, mut content: R) -> Result<()> {
         let dest_file = self.temp_dir.path().join(path.as_ref());
         let dest_dir = dest_file.parent().unwrap();
         if fs::metadata(dest_dir).is_err() {
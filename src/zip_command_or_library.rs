@@ -48,7 +48,7 @@ impl ZipCommandOrLibrary {
                 z.command(command);
                 z
             })
-            .and_then(|z| z.test().map(|_| z))
+            .and_then(|z| z.test().map(|()| z))
             .map(ZipCommandOrLibrary::Command)
             .or_else(|_| ZipLibrary::new().map(ZipCommandOrLibrary::Library))
     }
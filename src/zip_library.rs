@@ -4,18 +4,15 @@
 
 use crate::zip::Zip;
 use crate::Result;
+use crate::ZipStream;
 
 use std::fmt;
-use std::io;
 use std::io::Cursor;
 use std::io::Read;
 use std::io::Write;
 use std::path::Path;
 
 use color_eyre::eyre::Context;
-use libzip::write::FileOptions;
-use libzip::CompressionMethod;
-use libzip::ZipWriter;
 
 /// Zip files using the [Rust `zip`](https://crates.io/crates/zip) library.
 ///
@@ -23,10 +20,13 @@ use libzip::ZipWriter;
 /// run into some issues when trying to export EPUB generated with this method to
 /// ereaders (e.g. Kobo).
 ///
+/// The archive is kept in memory until the EPUB is generated: this is a
+/// [`ZipStream`](struct.ZipStream.html) writing into a `Cursor<Vec<u8>>`.
+///
 /// Note that these takes care of adding the mimetype (since it must not be deflated), it
 /// should not be added manually.
 pub struct ZipLibrary {
-    writer: ZipWriter<Cursor<Vec<u8>>>,
+    stream: ZipStream<Cursor<Vec<u8>>>,
 }
 
 impl fmt::Debug for ZipLibrary {
@@ -42,42 +42,19 @@ impl ZipLibrary {
     ///
     /// # Errors
     pub fn new() -> Result<Self> {
-        let mut writer = ZipWriter::new(Cursor::new(vec![]));
-        writer.set_comment(""); // Fix issues with some readers
-
-        writer
-            .start_file(
-                "mimetype",
-                FileOptions::default().compression_method(CompressionMethod::Stored),
-            )
-            .wrap_err("could not create mimetype in epub")?;
-        writer
-            .write(b"application/epub+zip")
-            .wrap_err("could not write mimetype in epub")?;
-
-        Ok(Self { writer })
+        Ok(Self {
+            stream: ZipStream::new(Cursor::new(vec![]))?,
+        })
     }
 }
 
 impl Zip for ZipLibrary {
-    fn write_file<P: AsRef<Path>, R: Read>(&mut self, path: P, mut content: R) -> Result<()> {
-        let mut file = format!("{}", path.as_ref().display());
-        if cfg!(target_os = "windows") {
-            // Path names should not use backspaces in zip files
-            file = file.replace('\\', "/");
-        }
-        let options = FileOptions::default();
-        self.writer
-            .start_file(file.clone(), options)
-            .wrap_err_with(|| format!("could not create file '{file}' in epub"))?;
-        io::copy(&mut content, &mut self.writer)
-            .wrap_err_with(|| format!("could not write file '{file}' in epub"))?;
-        Ok(())
+    fn write_file<P: AsRef<Path>, R: Read>(&mut self, path: P, content: R) -> Result<()> {
+        self.stream.write_file(path, content)
     }
 
     fn generate<W: Write>(&mut self, mut to: W) -> Result<()> {
-        let cursor = self.writer.finish().wrap_err("error writing zip file")?;
-        let bytes = cursor.into_inner();
+        let bytes = self.stream.finish_writer()?.into_inner();
         to.write_all(bytes.as_ref())
             .wrap_err("error writing zip file")?;
         Ok(())
@@ -0,0 +1,139 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with
+// this file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use crate::zip::Zip;
+use crate::Result;
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Read;
+use std::io::Seek;
+use std::io::Write;
+use std::path::Path;
+
+use color_eyre::eyre::{bail, Context};
+use libzip::write::FileOptions;
+use libzip::CompressionMethod;
+use libzip::ZipWriter;
+
+/// Zip files using the [Rust `zip`](https://crates.io/crates/zip) library, writing
+/// them straight into a destination as they are added.
+///
+/// Files are copied into the destination as they are added, without being kept in
+/// memory: only the central directory of the archive (and, in the builder, the `id`
+/// attributes of the documents, to check targets) is kept until the end, so
+/// peak memory use stays roughly constant, whatever the size of the book or of its
+/// resources (e.g. audio files).
+///
+/// The exception is the documents added with `add_content` that have to be
+/// transformed, i.e. when footnotes, `auto_toc` or `kepub` are used: each of them is
+/// then read whole in memory while it is processed.
+///
+/// The archive is completed with `Builder::finish`, and written into the destination
+/// given to `new` (or the file given to `create`): `Builder::generate`, which needs a
+/// writer, fails. To write into a `Cursor` or a `File` that you keep using
+/// afterwards, give a mutable reference to it.
+///
+/// Note that these takes care of adding the mimetype (since it must not be deflated), it
+/// should not be added manually.
+///
+/// # Example
+///
+/// ```
+/// use epub_builder::{Builder, ZipStream};
+/// use std::io;
+///
+/// let mut epub = io::Cursor::new(Vec::<u8>::new());
+/// Builder::new(ZipStream::new(&mut epub).unwrap())
+///     .unwrap()
+///     .add_resource("audio.mp3", b"Not really a MP3 file".as_ref(), "audio/mpeg")
+///     .unwrap()
+///     .finish()
+///     .unwrap();
+/// assert!(!epub.into_inner().is_empty());
+/// ```
+pub struct ZipStream<W: Write + Seek> {
+    writer: ZipWriter<W>,
+}
+
+impl<W: Write + Seek> fmt::Debug for ZipStream<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ZipStream")
+    }
+}
+
+impl<W: Write + Seek> ZipStream<W> {
+    /// Creates a new wrapper for zip library, writing into `to`
+    ///
+    /// Also add mimetype at the beginning of the EPUB file.
+    ///
+    /// # Errors
+    pub fn new(to: W) -> Result<Self> {
+        let mut writer = ZipWriter::new(to);
+        writer.set_comment(""); // Fix issues with some readers
+
+        writer
+            .start_file(
+                "mimetype",
+                FileOptions::default().compression_method(CompressionMethod::Stored),
+            )
+            .wrap_err("could not create mimetype in epub")?;
+        writer
+            .write(b"application/epub+zip")
+            .wrap_err("could not write mimetype in epub")?;
+
+        Ok(Self { writer })
+    }
+
+    /// Write the central directory, and return the destination
+    pub(crate) fn finish_writer(&mut self) -> Result<W> {
+        let mut to = self.writer.finish().wrap_err("error writing zip file")?;
+        to.flush().wrap_err("error writing zip file")?;
+        Ok(to)
+    }
+}
+
+impl ZipStream<BufWriter<File>> {
+    /// Creates a new wrapper for zip library, writing into the file at `path`, which
+    /// is created or truncated
+    ///
+    /// # Errors
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::create(path.as_ref())
+            .wrap_err_with(|| format!("could not create '{}'", path.as_ref().display()))?;
+        Self::new(BufWriter::new(file))
+    }
+}
+
+impl<W: Write + Seek> Zip for ZipStream<W> {
+    fn write_file<P: AsRef<Path>, R: Read>(&mut self, path: P, mut content: R) -> Result<()> {
+        let mut file = format!("{}", path.as_ref().display());
+        if cfg!(target_os = "windows") {
+            // Path names should not use backspaces in zip files
+            file = file.replace('\\', "/");
+        }
+        let options = FileOptions::default();
+        self.writer
+            .start_file(file.clone(), options)
+            .wrap_err_with(|| format!("could not create file '{file}' in epub"))?;
+        io::copy(&mut content, &mut self.writer)
+            .wrap_err_with(|| format!("could not write file '{file}' in epub"))?;
+        Ok(())
+    }
+
+    fn generate<T: Write>(&mut self, _: T) -> Result<()> {
+        bail!("the EPUB is written into the destination of the ZipStream: use `finish`")
+    }
+
+    fn is_streamed(&self) -> bool {
+        true
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.finish_writer()?;
+        Ok(())
+    }
+}